@@ -0,0 +1,62 @@
+//! Compare expanding `_LIST_` queries on every call with the bucketed, cached expansion
+//!
+//! Run with `cargo bench --bench list_query`
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use sqlx_type::{convert_list_query, list_bucket, QueryCache};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const QUERY: &str = "SELECT `id`, `ctext` FROM `t1` WHERE `cu8`=? AND `id` IN (_LIST_)";
+const CALLS: usize = 100_000;
+
+/// Run `f` for list lengths 1 to 100, returning the number of distinct queries
+fn run(name: &str, f: impl Fn(usize) -> usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let mut distinct = HashSet::new();
+    for i in 0..CALLS {
+        distinct.insert(f(i % 100 + 1));
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "{name:>10}: {:>8.1} ns/call, {:>6.3} allocations/call, {} distinct queries",
+        elapsed.as_nanos() as f64 / CALLS as f64,
+        allocations as f64 / CALLS as f64,
+        distinct.len()
+    );
+}
+
+fn main() {
+    run("expanded", |len| {
+        let query = convert_list_query(QUERY, &[len]);
+        std::hint::black_box(query.as_str()).len()
+    });
+
+    static CACHE: QueryCache = QueryCache::new();
+    run("cached", |len| {
+        let sizes = [list_bucket(len, 1, 65535)];
+        let query = CACHE.get(&sizes, || convert_list_query(QUERY, &sizes));
+        std::hint::black_box(query).len()
+    });
+}
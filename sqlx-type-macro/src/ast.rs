@@ -0,0 +1,273 @@
+//! Traversal of parsed statements
+use sql_parse::{
+    Expression, Identifier, JoinSpecification, QualifiedName, Select, SelectExpr, Statement,
+    TableReference,
+};
+
+fn visit_select_exprs<'a>(exprs: &[SelectExpr<'a>], f: &mut dyn FnMut(&Expression<'a>)) {
+    for e in exprs {
+        visit_expression(&e.expr, f);
+    }
+}
+
+fn visit_table_reference<'a>(t: &TableReference<'a>, f: &mut dyn FnMut(&Expression<'a>)) {
+    match t {
+        TableReference::Table { .. } => (),
+        TableReference::Query { query, .. } => visit_statement(query, f),
+        TableReference::Join {
+            left,
+            right,
+            specification,
+            ..
+        } => {
+            visit_table_reference(left, f);
+            visit_table_reference(right, f);
+            if let Some(JoinSpecification::On(e, _)) = specification {
+                visit_expression(e, f);
+            }
+        }
+    }
+}
+
+fn visit_select<'a>(s: &Select<'a>, f: &mut dyn FnMut(&Expression<'a>)) {
+    visit_select_exprs(&s.select_exprs, f);
+    for t in s.table_references.iter().flatten() {
+        visit_table_reference(t, f);
+    }
+    if let Some((e, _)) = &s.where_ {
+        visit_expression(e, f);
+    }
+    if let Some((_, exprs)) = &s.group_by {
+        for e in exprs {
+            visit_expression(e, f);
+        }
+    }
+    if let Some((e, _)) = &s.having {
+        visit_expression(e, f);
+    }
+    if let Some((_, order)) = &s.order_by {
+        for (e, _) in order {
+            visit_expression(e, f);
+        }
+    }
+}
+
+/// Call `f` for the expression and every expression it contains, including those in
+/// subqueries
+pub(crate) fn visit_expression<'a>(e: &Expression<'a>, f: &mut dyn FnMut(&Expression<'a>)) {
+    f(e);
+    match e {
+        Expression::Binary { lhs, rhs, .. } => {
+            visit_expression(lhs, f);
+            visit_expression(rhs, f);
+        }
+        Expression::Unary { operand, .. } => visit_expression(operand, f),
+        Expression::Subquery(s) | Expression::Exists(s) => visit_statement(s, f),
+        Expression::Function(_, args, _) => {
+            for a in args {
+                visit_expression(a, f);
+            }
+        }
+        Expression::WindowFunction {
+            args, window_spec, ..
+        } => {
+            for a in args {
+                visit_expression(a, f);
+            }
+            for (e, _) in &window_spec.order_by.1 {
+                visit_expression(e, f);
+            }
+        }
+        Expression::Extract { date, .. } => visit_expression(date, f),
+        Expression::In { lhs, rhs, .. } => {
+            visit_expression(lhs, f);
+            for e in rhs {
+                visit_expression(e, f);
+            }
+        }
+        Expression::Is(e, _, _) => visit_expression(e, f),
+        Expression::Case {
+            value,
+            whens,
+            else_,
+            ..
+        } => {
+            if let Some(value) = value {
+                visit_expression(value, f);
+            }
+            for w in whens {
+                visit_expression(&w.when, f);
+                visit_expression(&w.then, f);
+            }
+            if let Some((_, e)) = else_ {
+                visit_expression(e, f);
+            }
+        }
+        Expression::Cast { expr, .. }
+        | Expression::Count { expr, .. }
+        | Expression::GroupConcat { expr, .. } => visit_expression(expr, f),
+        Expression::TimestampAdd {
+            interval, datetime, ..
+        } => {
+            visit_expression(interval, f);
+            visit_expression(datetime, f);
+        }
+        Expression::TimestampDiff { e1, e2, .. } => {
+            visit_expression(e1, f);
+            visit_expression(e2, f);
+        }
+        Expression::Null(_)
+        | Expression::Bool(_, _)
+        | Expression::String(_)
+        | Expression::Integer(_)
+        | Expression::ListHack(_)
+        | Expression::Float(_)
+        | Expression::Identifier(_)
+        | Expression::Interval { .. }
+        | Expression::Arg(_)
+        | Expression::Invalid(_)
+        | Expression::Variable { .. } => (),
+    }
+}
+
+/// Call `f` for every expression in the statement, including those in subqueries
+pub(crate) fn visit_statement<'a>(s: &Statement<'a>, f: &mut dyn FnMut(&Expression<'a>)) {
+    match s {
+        Statement::Select(s) => visit_select(s, f),
+        Statement::Delete(d) => {
+            for t in &d.using {
+                visit_table_reference(t, f);
+            }
+            if let Some((e, _)) = &d.where_ {
+                visit_expression(e, f);
+            }
+            if let Some((_, exprs)) = &d.returning {
+                visit_select_exprs(exprs, f);
+            }
+        }
+        Statement::Update(u) => {
+            for t in &u.tables {
+                visit_table_reference(t, f);
+            }
+            for (_, e) in &u.set {
+                visit_expression(e, f);
+            }
+            if let Some((e, _)) = &u.where_ {
+                visit_expression(e, f);
+            }
+            if let Some((_, exprs)) = &u.returning {
+                visit_select_exprs(exprs, f);
+            }
+        }
+        Statement::InsertReplace(i) => {
+            for row in i.values.iter().flat_map(|(_, rows)| rows) {
+                for e in row {
+                    visit_expression(e, f);
+                }
+            }
+            if let Some(select) = &i.select {
+                visit_select(select, f);
+            }
+            for p in i.set.iter().flat_map(|s| &s.pairs) {
+                visit_expression(&p.value, f);
+            }
+            for p in i.on_duplicate_key_update.iter().flat_map(|s| &s.pairs) {
+                visit_expression(&p.value, f);
+            }
+            if let Some((_, exprs)) = &i.returning {
+                visit_select_exprs(exprs, f);
+            }
+        }
+        Statement::Union(u) => {
+            visit_statement(&u.left, f);
+            for w in &u.with {
+                visit_statement(&w.union_statement, f);
+            }
+            if let Some((_, order)) = &u.order_by {
+                for (e, _) in order {
+                    visit_expression(e, f);
+                }
+            }
+        }
+        Statement::WithQuery(w) => {
+            for b in &w.with_blocks {
+                visit_statement(&b.statement, f);
+            }
+            visit_statement(&w.statement, f);
+        }
+        _ => (),
+    }
+}
+
+type TableFn<'b, 'a> = &'b mut dyn FnMut(&QualifiedName<'a>, Option<&Identifier<'a>>);
+
+fn table_reference_tables<'a>(t: &TableReference<'a>, f: TableFn<'_, 'a>) {
+    match t {
+        TableReference::Table {
+            identifier, as_, ..
+        } => f(identifier, as_.as_ref()),
+        TableReference::Query { query, .. } => statement_tables(query, f),
+        TableReference::Join { left, right, .. } => {
+            table_reference_tables(left, f);
+            table_reference_tables(right, f);
+        }
+    }
+}
+
+/// The tables of the statement, excluding those of subqueries in expressions
+fn statement_tables<'a>(s: &Statement<'a>, f: TableFn<'_, 'a>) {
+    match s {
+        Statement::Select(s) => {
+            for t in s.table_references.iter().flatten() {
+                table_reference_tables(t, f);
+            }
+        }
+        Statement::Delete(d) => {
+            for t in &d.tables {
+                f(t, None);
+            }
+            for t in &d.using {
+                table_reference_tables(t, f);
+            }
+        }
+        Statement::Update(u) => {
+            for t in &u.tables {
+                table_reference_tables(t, f);
+            }
+        }
+        Statement::InsertReplace(i) => {
+            f(&i.table, None);
+            for t in i
+                .select
+                .iter()
+                .flat_map(|s| s.table_references.iter().flatten())
+            {
+                table_reference_tables(t, f);
+            }
+        }
+        Statement::Union(u) => {
+            statement_tables(&u.left, f);
+            for w in &u.with {
+                statement_tables(&w.union_statement, f);
+            }
+        }
+        Statement::WithQuery(w) => {
+            for b in &w.with_blocks {
+                statement_tables(&b.statement, f);
+            }
+            statement_tables(&w.statement, f);
+        }
+        _ => (),
+    }
+}
+
+/// Call `f` with the name and alias of every table in the statement, including those in
+/// subqueries
+pub(crate) fn visit_tables<'a>(s: &Statement<'a>, f: TableFn<'_, 'a>) {
+    statement_tables(s, f);
+    visit_statement(s, &mut |e| {
+        if let Expression::Subquery(s) | Expression::Exists(s) = e {
+            statement_tables(s, f);
+        }
+    });
+}
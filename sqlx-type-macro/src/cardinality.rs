@@ -0,0 +1,326 @@
+//! Inference of the number of rows returned by a statement
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use once_cell::sync::Lazy;
+use sql_parse::{
+    AlterSpecification, BinaryOperator, CreateDefinition, CreateOption, DataTypeProperty,
+    Expression, Function, IdentifierPart, IndexType, ParseOptions, SQLDialect, Select, Span,
+    Statement, TableReference,
+};
+
+use crate::{custom, SCHEMAS};
+
+/// The number of rows a statement may return
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Cardinality {
+    /// The statement returns no rows
+    None,
+    /// The statement returns exactly one row
+    One,
+    /// The statement returns zero or one row
+    Optional,
+    /// The statement may return any number of rows
+    Many,
+}
+
+/// Map from lower case table name to the sets of columns that are unique in the table
+static UNIQUE_KEYS: Lazy<BTreeMap<String, Vec<Vec<String>>>> = Lazy::new(|| {
+    let (_, dialect) = SCHEMAS.deref();
+    let options = ParseOptions::new().dialect(dialect.clone());
+    let parsable = custom::parsable();
+    let mut issues = sql_parse::Issues::new(parsable);
+    // Issues has already been reported when parsing the schemas
+    let statements = sql_parse::parse_statements(parsable, &mut issues, &options);
+
+    let mut keys: BTreeMap<String, Vec<Vec<String>>> = BTreeMap::new();
+    for (table, columns) in custom::keys() {
+        keys.entry(table.clone()).or_default().push(columns.clone());
+    }
+    let is_key = |properties: &[DataTypeProperty]| {
+        properties.iter().any(|p| {
+            matches!(
+                p,
+                DataTypeProperty::PrimaryKey(_)
+                    | DataTypeProperty::Unique(_)
+                    | DataTypeProperty::UniqueKey(_)
+            )
+        })
+    };
+    for statement in statements {
+        match statement {
+            Statement::CreateTable(t) => {
+                let table = t.identifier.identifier.value.to_lowercase();
+                for d in t.create_definitions {
+                    if let CreateDefinition::ColumnDefinition {
+                        identifier,
+                        data_type,
+                    } = d
+                    {
+                        if is_key(&data_type.properties) {
+                            keys.entry(table.clone())
+                                .or_default()
+                                .push(vec![identifier.value.to_lowercase()]);
+                        }
+                    }
+                }
+            }
+            Statement::AlterTable(a) => {
+                let table = a.table.identifier.value.to_lowercase();
+                for s in a.alter_specifications {
+                    match s {
+                        AlterSpecification::AddIndex {
+                            index_type: IndexType::Primary(_) | IndexType::Unique(_),
+                            cols,
+                            ..
+                        } => keys
+                            .entry(table.clone())
+                            .or_default()
+                            .push(cols.iter().map(|c| c.name.value.to_lowercase()).collect()),
+                        AlterSpecification::Modify {
+                            col, definition, ..
+                        } if is_key(&definition.properties) => keys
+                            .entry(table.clone())
+                            .or_default()
+                            .push(vec![col.value.to_lowercase()]),
+                        _ => (),
+                    }
+                }
+            }
+            Statement::CreateIndex(i)
+                if i.where_.is_none()
+                    && i.create_options
+                        .iter()
+                        .any(|o| matches!(o, CreateOption::Unique(_))) =>
+            {
+                keys.entry(i.table_name.identifier.value.to_lowercase())
+                    .or_default()
+                    .push(
+                        i.column_names
+                            .iter()
+                            .map(|c| c.value.to_lowercase())
+                            .collect(),
+                    );
+            }
+            _ => (),
+        }
+    }
+    keys
+});
+
+/// Return true if the expression is an aggregate, or contains one outside of a subquery
+fn has_aggregate(e: &Expression) -> bool {
+    match e {
+        Expression::Count { .. } | Expression::GroupConcat { .. } => true,
+        Expression::Function(
+            Function::Max | Function::Min | Function::Sum | Function::JsonArrayAgg,
+            _,
+            _,
+        ) => true,
+        Expression::Function(_, args, _) => args.iter().any(has_aggregate),
+        Expression::Binary { lhs, rhs, .. } => has_aggregate(lhs) || has_aggregate(rhs),
+        Expression::Unary { operand, .. } => has_aggregate(operand),
+        Expression::Cast { expr, .. } => has_aggregate(expr),
+        Expression::Is(e, _, _) => has_aggregate(e),
+        _ => false,
+    }
+}
+
+/// Return true if the expression does not depend on the row
+fn is_constant(e: &Expression) -> bool {
+    matches!(
+        e,
+        Expression::Arg(_)
+            | Expression::Integer(_)
+            | Expression::String(_)
+            | Expression::Float(_)
+            | Expression::Bool(..)
+            | Expression::Null(_)
+    )
+}
+
+/// Collect the lower case names of columns of the table compared for equality with
+/// constants in a conjunction
+fn equal_columns(e: &Expression, names: &[&str], out: &mut Vec<String>) {
+    let Expression::Binary { op, lhs, rhs, .. } = e else {
+        return;
+    };
+    match op {
+        BinaryOperator::And => {
+            equal_columns(lhs, names, out);
+            equal_columns(rhs, names, out);
+        }
+        // `<=>` matches every null of a nullable unique column
+        BinaryOperator::Eq => {
+            let (column, value) = if is_constant(rhs) {
+                (lhs, rhs)
+            } else {
+                (rhs, lhs)
+            };
+            if !is_constant(value) {
+                return;
+            }
+            let Expression::Identifier(parts) = column.as_ref() else {
+                return;
+            };
+            let column = match parts.as_slice() {
+                [IdentifierPart::Name(c)] => c,
+                [IdentifierPart::Name(t), IdentifierPart::Name(c)]
+                    if names.iter().any(|n| n.eq_ignore_ascii_case(t.value)) =>
+                {
+                    c
+                }
+                _ => return,
+            };
+            out.push(column.value.to_lowercase());
+        }
+        _ => (),
+    }
+}
+
+/// Return true if the offset or limit may leave out the row of an aggregate
+fn limit_skips_row(limit: &Option<(Span, Option<Expression>, Expression)>) -> bool {
+    match limit {
+        None => false,
+        Some((_, offset, limit)) => {
+            !matches!(offset, None | Some(Expression::Integer((0, _))))
+                || !matches!(limit, Expression::Integer((1.., _)))
+        }
+    }
+}
+
+fn select_cardinality(select: &Select) -> Cardinality {
+    if select.group_by.is_none() && select.select_exprs.iter().any(|e| has_aggregate(&e.expr)) {
+        return if select.having.is_some() || limit_skips_row(&select.limit) {
+            Cardinality::Optional
+        } else {
+            Cardinality::One
+        };
+    }
+    if let Some((_, _, Expression::Integer((0 | 1, _)))) = &select.limit {
+        return Cardinality::Optional;
+    }
+    let table = match select.table_references.as_deref() {
+        None => {
+            return if select.where_.is_some() {
+                Cardinality::Optional
+            } else {
+                Cardinality::One
+            }
+        }
+        Some(
+            [TableReference::Table {
+                identifier, as_, ..
+            }],
+        ) if identifier.prefix.is_empty() => (identifier.identifier.value, as_),
+        Some(_) => return Cardinality::Many,
+    };
+    let (Some((where_, _)), Some(keys)) =
+        (&select.where_, UNIQUE_KEYS.get(&table.0.to_lowercase()))
+    else {
+        return Cardinality::Many;
+    };
+    let mut names = vec![table.0];
+    if let Some(as_) = table.1 {
+        names.push(as_.value);
+    }
+    let mut columns = Vec::new();
+    equal_columns(where_, &names, &mut columns);
+    if keys
+        .iter()
+        .any(|key| key.iter().all(|c| columns.contains(c)))
+    {
+        Cardinality::Optional
+    } else {
+        Cardinality::Many
+    }
+}
+
+/// Infer the number of rows returned by the statement
+pub(crate) fn cardinality(statement: &str, dialect: &SQLDialect) -> Cardinality {
+    let options = crate::parse_options(dialect);
+    let mut issues = sql_parse::Issues::new(statement);
+    match sql_parse::parse_statement(statement, &mut issues, &options) {
+        Some(Statement::Select(s)) => select_cardinality(&s),
+        Some(Statement::InsertReplace(i)) if i.returning.is_some() => match &i.values {
+            Some((_, rows))
+                if rows.len() == 1
+                    && !rows[0].iter().any(|e| matches!(e, Expression::ListHack(_))) =>
+            {
+                Cardinality::One
+            }
+            _ => Cardinality::Many,
+        },
+        Some(Statement::Delete(d)) if d.returning.is_some() => Cardinality::Many,
+        Some(Statement::Update(u)) if u.returning.is_some() => Cardinality::Many,
+        Some(Statement::InsertReplace(_) | Statement::Delete(_) | Statement::Update(_)) => {
+            Cardinality::None
+        }
+        _ => Cardinality::Many,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cardinality() {
+        let d = SQLDialect::MariaDB;
+        assert_eq!(
+            cardinality("SELECT COUNT(*) FROM `t1` WHERE `cu8`=?", &d),
+            Cardinality::One
+        );
+        assert_eq!(
+            cardinality("SELECT `cu8`, COUNT(*) FROM `t1` GROUP BY `cu8`", &d),
+            Cardinality::Many
+        );
+        assert_eq!(
+            cardinality("SELECT COUNT(*) FROM `t1` LIMIT 1", &d),
+            Cardinality::One
+        );
+        assert_eq!(
+            cardinality("SELECT COUNT(*) FROM `t1` LIMIT 0", &d),
+            Cardinality::Optional
+        );
+        assert_eq!(
+            cardinality("SELECT COUNT(*) FROM `t1` LIMIT 1 OFFSET 1", &d),
+            Cardinality::Optional
+        );
+        assert_eq!(
+            cardinality("SELECT COUNT(*) FROM `t1` LIMIT 1, 1", &d),
+            Cardinality::Optional
+        );
+        assert_eq!(
+            cardinality("SELECT COUNT(*) FROM `t1` LIMIT ?", &d),
+            Cardinality::Optional
+        );
+        assert_eq!(
+            cardinality(
+                "SELECT `ctext` FROM `t1` AS `t` WHERE `t`.`id`=? AND `cu8`=7",
+                &d
+            ),
+            Cardinality::Optional
+        );
+        assert_eq!(
+            cardinality("SELECT `ctext` FROM `t1` WHERE `id`<=>?", &d),
+            Cardinality::Many
+        );
+        assert_eq!(
+            cardinality("SELECT `ctext` FROM `t1` WHERE `id`=? OR `cu8`=7", &d),
+            Cardinality::Many
+        );
+        assert_eq!(
+            cardinality("SELECT `ctext` FROM `t1` WHERE `cu8`=? LIMIT 1", &d),
+            Cardinality::Optional
+        );
+        assert_eq!(
+            cardinality("SELECT `ctext` FROM `t1` WHERE `id` IN (_LIST_)", &d),
+            Cardinality::Many
+        );
+        assert_eq!(
+            cardinality("DELETE FROM `t1` WHERE `id`=?", &d),
+            Cardinality::None
+        );
+    }
+}
@@ -0,0 +1,929 @@
+//! Columns of types unknown to sql-type
+//!
+//! sql-type does not know decimal, UUID and array types, so `DECIMAL(p, s)` and
+//! `NUMERIC(p, s)` are replaced by `FLOAT8`, `UUID` by `CHAR` and the `[]` of array types by
+//! spaces, padded to the same length, before the schema is parsed. Binary and text columns
+//! holding UUIDs are annotated with a `-- sqlx-type: uuid` comment on the line of the column,
+//! and arrays that may hold nulls with `-- sqlx-type: nullable elements`.
+//!
+//! For PostgreSQL `timestamp` without time zone is replaced by `DATETIME`, JSON, interval and
+//! network address columns, which sql-type takes to be text, are recorded, as are the
+//! integer types `integer`, `serial` and the like, which it takes to be any integer or text,
+//! and `CREATE TYPE ... AS ENUM` statements are blanked out and their types recorded.
+//!
+//! The primary and unique keys defined among the columns of `CREATE TABLE` statements, which
+//! sql-parse does not parse, are blanked out and recorded.
+//!
+//! The lengths of `VARCHAR(n)` and `CHAR(n)` columns, which sql-type does not keep, are
+//! recorded for checking literal arguments, except for SQLite which does not enforce them.
+//!
+//! For SQLite the types of the columns of `STRICT` tables, and the types sql-parse does not
+//! know, are replaced by a type of the same affinity, and the column is given the base type of
+//! the affinity, `ANY` and numeric affinity being any. `WITHOUT ROWID` is blanked out.
+//!
+//! The columns are then given a type of their own, an argument type without any arguments of
+//! the base type of the column, or of its elements for arrays. It is typed as its base type
+//! in expressions, but is passed on unchanged to the columns selecting the column and to the
+//! arguments inserted into it. What the type of each column stands for is found by the
+//! address of the type.
+use std::ops::Range;
+use std::sync::Arc;
+
+use once_cell::sync::{Lazy, OnceCell};
+use sql_type::schema::Schemas;
+use sql_type::{BaseType, FullType, SQLDialect, Type};
+
+use crate::lists::{tokenize, Kind, Token};
+use crate::SCHEMA_SRC;
+
+/// The precision and scale of a decimal column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Decimal {
+    /// The total number of digits, None if not given in the schema which is 10 for MariaDB
+    /// and unconstrained for PostgreSQL
+    pub precision: Option<u32>,
+    /// The number of digits after the decimal point
+    pub scale: u32,
+}
+
+/// A PostgreSQL enum type defined by `CREATE TYPE name AS ENUM (...)`
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Enum {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+/// The type of a column unknown to sql-type
+#[derive(Clone, Debug)]
+pub(crate) enum Custom {
+    Decimal(Decimal),
+    Uuid,
+    /// A PostgreSQL `uuid`, compared with text but encoded as binary
+    PgUuid,
+    /// An array with elements of the given type
+    Array(FullType<'static>),
+    Json,
+    Interval,
+    Inet,
+    /// The enum type with the given index in [enums]
+    Enum(usize),
+    /// Text of at most the given number of characters
+    Varchar(u32),
+}
+
+/// A type unknown to sql-type or an annotation found in the schema
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Found {
+    Decimal(Decimal),
+    Uuid,
+    PgUuid,
+    Array,
+    NullableElements,
+    Json,
+    Interval,
+    Inet,
+    Enum(usize),
+    /// The maximum number of characters of a text column
+    Length(u32),
+    /// A column of a type sql-type does not type correctly, such as a SQLite column typed by
+    /// its affinity
+    Type(Type<'static>),
+}
+
+/// The schema with unknown types replaced, the types and annotations found, the enum types
+/// and the keys of the tables
+type Replaced = (String, Vec<(usize, Found)>, Vec<Enum>, Vec<Key>);
+
+/// The lower case names of a table and of the columns of a primary or unique key of it
+type Key = (String, Vec<String>);
+
+/// The schema with unknown types replaced, the types and annotations found with their
+/// position, which is at or after the end of the name of the column they belong to, the
+/// enum types defined and the keys defined among the columns of tables
+static PARSABLE: Lazy<Replaced> = Lazy::new(|| {
+    let schema = SCHEMA_SRC.as_str();
+    replace(schema, &crate::dialect(schema))
+});
+
+/// The address of the type of each column of an unknown type, and what it stands for
+static COLUMNS: OnceCell<Vec<(usize, Custom)>> = OnceCell::new();
+
+/// The schema with the types unknown to sql-type replaced, with the same offsets
+pub(crate) fn parsable() -> &'static str {
+    PARSABLE.0.as_str()
+}
+
+/// The enum types defined in the schema
+pub(crate) fn enums() -> &'static [Enum] {
+    &PARSABLE.2
+}
+
+/// The primary and unique keys defined among the columns of tables, blanked out of [parsable]
+pub(crate) fn keys() -> &'static [Key] {
+    &PARSABLE.3
+}
+
+/// The name of the Rust enum of an enum type or of the variant for a value, in camel case
+pub(crate) fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    for part in name.split(|c: char| !c.is_alphanumeric()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            out.extend(first.to_uppercase());
+            out.push_str(chars.as_str());
+        }
+    }
+    if !out.starts_with(|c: char| c.is_alphabetic()) {
+        out.insert(0, 'V');
+    }
+    out
+}
+
+/// Spaces in place of text, keeping its newlines and length
+fn blank(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c == '\n' {
+                "\n".to_string()
+            } else {
+                " ".repeat(c.len_utf8())
+            }
+        })
+        .collect()
+}
+
+/// The quoted strings in text, where a quote in a string is doubled
+fn strings(text: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\'' {
+            continue;
+        }
+        let mut string = String::new();
+        while let Some(c) = chars.next() {
+            if c == '\'' && chars.next_if_eq(&'\'').is_none() {
+                break;
+            }
+            string.push(c);
+        }
+        strings.push(string);
+    }
+    strings
+}
+
+/// True if the token is a comment
+fn is_comment(schema: &str, t: &Token) -> bool {
+    let text = &schema[t.start..t.end];
+    t.kind == Kind::Other && (text.starts_with("--") || text.starts_with("/*"))
+}
+
+/// The indices of the tokens naming the columns defined by CREATE TABLE statements, and
+/// whether their table is STRICT
+fn column_names(schema: &str, tokens: &[Token]) -> Vec<(usize, bool)> {
+    let keyword = |i: usize, k: &str| {
+        tokens.get(i).is_some_and(|t| t.kind == Kind::Word)
+            && schema[tokens[i].start..tokens[i].end].eq_ignore_ascii_case(k)
+    };
+    let mut names = Vec::new();
+    let mut depth = 0;
+    // Set after TABLE until the columns are opened
+    let mut table = false;
+    // The depth of the columns and the index of the first column name of the open table
+    let mut columns: Option<(usize, usize)> = None;
+    let mut previous = None;
+    for (i, t) in tokens.iter().enumerate() {
+        let text = &schema[t.start..t.end];
+        match t.kind {
+            Kind::Open => {
+                depth += 1;
+                if table {
+                    columns = Some((depth, names.len()));
+                    table = false;
+                }
+            }
+            Kind::Close => {
+                if let Some((d, first)) = columns {
+                    if d == depth {
+                        // The table options follow the columns until the end of the statement
+                        let strict = (i + 1..tokens.len())
+                            .take_while(|j| &schema[tokens[*j].start..tokens[*j].end] != ";")
+                            .any(|j| keyword(j, "STRICT"));
+                        for (_, s) in &mut names[first..] {
+                            *s = strict;
+                        }
+                        columns = None;
+                    }
+                }
+                depth = depth.saturating_sub(1);
+            }
+            _ if keyword(i, "TABLE") => table = true,
+            _ if keyword(i, "AS") || text == ";" => table = false,
+            _ => (),
+        }
+        let is_name = t.kind == Kind::Word || text.starts_with('"') || text.starts_with('`');
+        if is_name
+            && columns.is_some_and(|(d, _)| d == depth)
+            && previous.is_some_and(|p: usize| matches!(tokens[p].kind, Kind::Open | Kind::Comma))
+            && !["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+                .iter()
+                .any(|k| keyword(i, k))
+        {
+            names.push((i, false));
+        }
+        if !is_comment(schema, t) {
+            previous = Some(i);
+        }
+    }
+    names
+}
+
+/// The primary and unique keys defined among the columns of CREATE TABLE statements, with
+/// the range to blank out, which takes the comma separating them from the other definitions
+fn key_definitions(schema: &str, tokens: &[Token]) -> Vec<(Range<usize>, Key)> {
+    let text = |i: usize| &schema[tokens[i].start..tokens[i].end];
+    let keyword = |i: usize, k: &str| {
+        tokens.get(i).is_some_and(|t| t.kind == Kind::Word) && text(i).eq_ignore_ascii_case(k)
+    };
+    let kind = |i: usize| tokens.get(i).map(|t| t.kind);
+    let name = |i: usize| text(i).trim_matches(['`', '"']).to_lowercase();
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let create = keyword(i, "TABLE")
+            && ["CREATE", "TEMPORARY", "TEMP"]
+                .iter()
+                .any(|k| keyword(i.wrapping_sub(1), k));
+        let open = (i..tokens.len())
+            .take_while(|j| create && text(*j) != ";")
+            .find(|j| tokens[*j].kind == Kind::Open);
+        let Some(open) = open.filter(|o| *o > i + 1) else {
+            i += 1;
+            continue;
+        };
+        let table = name(open - 1);
+        // The tokens of each definition without comments, and the comma or parenthesis ending it
+        let mut definitions = Vec::new();
+        let mut definition = Vec::new();
+        let mut depth = 0;
+        let mut j = open + 1;
+        while j < tokens.len() {
+            match tokens[j].kind {
+                Kind::Close if depth == 0 => break,
+                Kind::Comma if depth == 0 => {
+                    definitions.push((std::mem::take(&mut definition), j));
+                    j += 1;
+                    continue;
+                }
+                Kind::Open => depth += 1,
+                Kind::Close => depth -= 1,
+                _ => (),
+            }
+            if !is_comment(schema, &tokens[j]) {
+                definition.push(j);
+            }
+            j += 1;
+        }
+        definitions.push((definition, j));
+        for (n, (definition, end)) in definitions.iter().enumerate() {
+            let at = |k: usize| definition.get(k).copied().unwrap_or(usize::MAX);
+            let mut k = if keyword(at(0), "CONSTRAINT") { 2 } else { 0 };
+            if keyword(at(k), "PRIMARY") && keyword(at(k + 1), "KEY") {
+                k += 2;
+            } else if keyword(at(k), "UNIQUE") {
+                k += 1;
+                if keyword(at(k), "KEY") || keyword(at(k), "INDEX") {
+                    k += 1;
+                }
+                if kind(at(k)).is_some_and(|k| k != Kind::Open) {
+                    k += 1;
+                }
+            } else {
+                continue;
+            }
+            if kind(at(k)) != Some(Kind::Open) {
+                continue;
+            }
+            // The names directly inside the parentheses, not the lengths of prefixes
+            let mut columns = Vec::new();
+            let mut depth = 0;
+            for (previous, c) in definition[k..].iter().zip(&definition[k + 1..]) {
+                match tokens[*previous].kind {
+                    Kind::Open => depth += 1,
+                    Kind::Close => depth -= 1,
+                    _ => (),
+                }
+                if depth == 0 {
+                    break;
+                }
+                if depth == 1
+                    && matches!(tokens[*previous].kind, Kind::Open | Kind::Comma)
+                    && matches!(kind(*c), Some(Kind::Word | Kind::Other))
+                {
+                    columns.push(name(*c));
+                }
+            }
+            let range = if n > 0 {
+                tokens[definitions[n - 1].1].start..tokens[end - 1].end
+            } else if definitions.len() > 1 {
+                tokens[definition[0]].start..tokens[*end].end
+            } else {
+                tokens[definition[0]].start..tokens[end - 1].end
+            };
+            keys.push((range, (table.clone(), columns)));
+        }
+        i = j;
+    }
+    keys
+}
+
+/// True if sql-parse knows the type of a SQLite column
+fn known_sqlite_type(type_: &str) -> bool {
+    let statement = format!("CREATE TABLE t (c {type_})");
+    let mut issues = sql_parse::Issues::new(&statement);
+    let options = sql_parse::ParseOptions::new().dialect(SQLDialect::Sqlite);
+    sql_parse::parse_statement(&statement, &mut issues, &options);
+    issues.is_ok()
+}
+
+/// A type known to sql-parse of the affinity SQLite gives the type, and the base type of the
+/// affinity
+fn affinity(type_: &str) -> (&'static str, BaseType) {
+    let type_ = type_.to_ascii_uppercase();
+    if type_.contains("INT") {
+        ("INT", BaseType::Integer)
+    } else if ["CHAR", "CLOB", "TEXT"].iter().any(|k| type_.contains(k)) {
+        ("TEXT", BaseType::String)
+    } else if type_.contains("BLOB") {
+        ("BLOB", BaseType::Bytes)
+    } else if ["REAL", "FLOA", "DOUB"].iter().any(|k| type_.contains(k)) {
+        ("REAL", BaseType::Float)
+    } else {
+        ("INT", BaseType::Any)
+    }
+}
+
+/// The annotation of a comment, if it is of the form `-- sqlx-type: annotation`
+pub(crate) fn annotation(comment: &str) -> Option<&str> {
+    let text = match comment.strip_prefix("--") {
+        Some(text) => text,
+        None => comment.strip_prefix("/*")?.strip_suffix("*/")?,
+    };
+    Some(text.trim().strip_prefix("sqlx-type:")?.trim())
+}
+
+/// Replace the unknown types following a column name in schema by known types
+fn replace(schema: &str, dialect: &SQLDialect) -> Replaced {
+    let postgres = matches!(dialect, SQLDialect::PostgreSQL);
+    let sqlite = matches!(dialect, SQLDialect::Sqlite);
+    let tokens = tokenize(schema);
+    let names = column_names(schema, &tokens);
+    let keys = key_definitions(schema, &tokens);
+    let text = |i: usize| tokens.get(i).map(|t| &schema[t.start..t.end]);
+    let number = |i: usize| text(i).and_then(|t| t.parse::<u32>().ok());
+    let keyword = |i: usize, k: &str| {
+        tokens.get(i).is_some_and(|t| t.kind == Kind::Word)
+            && text(i).unwrap().eq_ignore_ascii_case(k)
+    };
+    let is_name = |i: usize| {
+        tokens[i].kind == Kind::Word
+            || matches!(text(i), Some(n) if n.starts_with('`') || n.starts_with('"'))
+    };
+    let mut out = schema.to_string();
+    for (range, _) in &keys {
+        out.replace_range(range.clone(), &blank(&schema[range.clone()]));
+    }
+    let mut found = Vec::new();
+    let mut enums = Vec::new();
+    let mut skip = 0;
+    for (i, t) in tokens.iter().enumerate() {
+        if i < skip || keys.iter().any(|(range, _)| range.contains(&t.start)) {
+            continue;
+        }
+        let word = &schema[t.start..t.end];
+        if postgres
+            && keyword(i, "CREATE")
+            && keyword(i + 1, "TYPE")
+            && keyword(i + 3, "AS")
+            && keyword(i + 4, "ENUM")
+            && tokens.get(i + 5).is_some_and(|t| t.kind == Kind::Open)
+        {
+            let Some(close) = tokens[i + 5..]
+                .iter()
+                .position(|t| t.kind == Kind::Close)
+                .map(|c| c + i + 5)
+            else {
+                continue;
+            };
+            let values = strings(&schema[tokens[i + 5].end..tokens[close].start]);
+            enums.push(Enum {
+                name: text(i + 2).unwrap().to_string(),
+                values,
+            });
+            let end = if text(close + 1) == Some(";") {
+                close + 1
+            } else {
+                close
+            };
+            let range = t.start..tokens[end].end;
+            out.replace_range(range.clone(), &blank(&schema[range]));
+            skip = end + 1;
+            continue;
+        }
+        if let Some(annotation) = annotation(word) {
+            // The column is the first name on the line of the annotation
+            let line = schema[..t.start].rfind('\n').map_or(0, |p| p + 1);
+            let Some(first) = tokens[..i].iter().position(|t| t.start >= line) else {
+                continue;
+            };
+            if !is_name(first) {
+                continue;
+            }
+            for annotation in annotation.split(',').map(str::trim) {
+                if annotation.eq_ignore_ascii_case("uuid") {
+                    found.push((tokens[first].end, Found::Uuid));
+                } else if annotation.eq_ignore_ascii_case("nullable elements") {
+                    found.push((tokens[first].end, Found::NullableElements));
+                }
+            }
+            continue;
+        }
+        if t.kind == Kind::OpenBracket
+            && tokens
+                .get(i + 1)
+                .is_some_and(|t| t.kind == Kind::CloseBracket)
+        {
+            out.replace_range(t.start..tokens[i + 1].end, "  ");
+            found.push((t.start, Found::Array));
+            continue;
+        }
+        if sqlite && keyword(i, "WITHOUT") && keyword(i + 1, "ROWID") {
+            // Along with the comma separating it from the other table options
+            let start = if i > 0 && text(i - 1) == Some(",") {
+                i - 1
+            } else {
+                i
+            };
+            let end = if text(i + 2) == Some(",") {
+                i + 2
+            } else {
+                i + 1
+            };
+            let range = tokens[start].start..tokens[end].end;
+            out.replace_range(range.clone(), &blank(&schema[range]));
+            skip = end + 1;
+            continue;
+        }
+        if t.kind != Kind::Word || i == 0 || !is_name(i - 1) {
+            continue;
+        }
+        let name_end = tokens[i - 1].end;
+        let column = names.iter().find(|(n, _)| *n == i - 1);
+        // Only the type of a column, not a column named uuid or the function in a default
+        if column.is_some() && word.eq_ignore_ascii_case("UUID") {
+            out.replace_range(t.start..t.end, "CHAR");
+            found.push((name_end, if postgres { Found::PgUuid } else { Found::Uuid }));
+            continue;
+        }
+        if let Some((_, strict)) = column.filter(|_| sqlite) {
+            // The type is the words up to the constraints of the column, and its arguments
+            let mut end = i;
+            while tokens.get(end).is_some_and(|t| t.kind == Kind::Word)
+                && ![
+                    "CONSTRAINT",
+                    "PRIMARY",
+                    "NOT",
+                    "NULL",
+                    "UNIQUE",
+                    "CHECK",
+                    "DEFAULT",
+                    "COLLATE",
+                    "REFERENCES",
+                    "GENERATED",
+                    "AS",
+                ]
+                .iter()
+                .any(|k| keyword(end, k))
+            {
+                end += 1;
+            }
+            if tokens.get(end).is_some_and(|t| t.kind == Kind::Open) {
+                match tokens[end..].iter().position(|t| t.kind == Kind::Close) {
+                    Some(close) => end += close + 1,
+                    None => continue,
+                }
+            }
+            let range = t.start..tokens[end - 1].end;
+            let type_ = &schema[range.clone()];
+            let decimal =
+                word.eq_ignore_ascii_case("DECIMAL") || word.eq_ignore_ascii_case("NUMERIC");
+            if !decimal && (*strict || !known_sqlite_type(type_)) {
+                let (known, base) = affinity(type_);
+                // Types shorter than the known type are left for sql-parse to report
+                if known.len() <= type_.len() {
+                    let padding = blank(&type_[known.len()..]);
+                    out.replace_range(range, &format!("{known}{padding}"));
+                    found.push((name_end, Found::Type(Type::Base(base))));
+                    skip = end;
+                    continue;
+                }
+            }
+        }
+        if column.is_some()
+            && ["VARCHAR", "CHAR", "CHARACTER", "NCHAR", "NVARCHAR"]
+                .iter()
+                .any(|k| word.eq_ignore_ascii_case(k))
+        {
+            let mut open = i + 1;
+            if keyword(open, "VARYING") {
+                open += 1;
+            }
+            let kind = |i: usize| tokens.get(i).map(|t| t.kind);
+            if let (Some(Kind::Open), Some(length), Some(Kind::Close)) =
+                (kind(open), number(open + 1), kind(open + 2))
+            {
+                found.push((name_end, Found::Length(length)));
+            }
+            continue;
+        }
+        if postgres {
+            // sql-type types integer as any integer, and the other aliases as text
+            let integer = if ["INTEGER", "INT4", "SERIAL"]
+                .iter()
+                .any(|k| word.eq_ignore_ascii_case(k))
+            {
+                Some(Type::I32)
+            } else if ["INT8", "BIGSERIAL"]
+                .iter()
+                .any(|k| word.eq_ignore_ascii_case(k))
+            {
+                Some(Type::I64)
+            } else if ["INT2", "SMALLSERIAL"]
+                .iter()
+                .any(|k| word.eq_ignore_ascii_case(k))
+            {
+                Some(Type::I16)
+            } else {
+                None
+            };
+            if let Some(integer) = integer {
+                let padding = blank(&word[3..]);
+                out.replace_range(t.start..t.end, &format!("INT{padding}"));
+                found.push((name_end, Found::Type(integer)));
+                continue;
+            }
+            let pg = if word.eq_ignore_ascii_case("JSON") || word.eq_ignore_ascii_case("JSONB") {
+                Some(Found::Json)
+            } else if word.eq_ignore_ascii_case("INTERVAL") {
+                Some(Found::Interval)
+            } else if word.eq_ignore_ascii_case("INET") || word.eq_ignore_ascii_case("CIDR") {
+                Some(Found::Inet)
+            } else {
+                enums
+                    .iter()
+                    .position(|e: &Enum| e.name.eq_ignore_ascii_case(word))
+                    .map(Found::Enum)
+            };
+            if let Some(pg) = pg {
+                found.push((name_end, pg));
+                continue;
+            }
+        }
+        if postgres && word.eq_ignore_ascii_case("TIMESTAMP") {
+            // A timestamp without time zone is a datetime, one with time zone is left as is
+            let mut next = i + 1;
+            if tokens.get(next).is_some_and(|t| t.kind == Kind::Open) {
+                next += 3;
+            }
+            if keyword(next, "WITH") {
+                continue;
+            }
+            out.replace_range(t.start..t.end, "DATETIME ");
+            if keyword(next, "WITHOUT") && keyword(next + 1, "TIME") && keyword(next + 2, "ZONE") {
+                let range = tokens[next].start..tokens[next + 2].end;
+                out.replace_range(range.clone(), &blank(&schema[range]));
+            }
+            continue;
+        }
+        if !word.eq_ignore_ascii_case("DECIMAL") && !word.eq_ignore_ascii_case("NUMERIC") {
+            continue;
+        }
+        let mut end = i + 1;
+        let mut decimal = Decimal {
+            precision: None,
+            scale: 0,
+        };
+        if tokens.get(end).is_some_and(|t| t.kind == Kind::Open) {
+            match (number(end + 1), tokens.get(end + 2).map(|t| t.kind)) {
+                (Some(precision), Some(Kind::Close)) => {
+                    decimal.precision = Some(precision);
+                    end += 3;
+                }
+                (Some(precision), Some(Kind::Comma)) => {
+                    let (Some(scale), Some(Kind::Close)) =
+                        (number(end + 3), tokens.get(end + 4).map(|t| t.kind))
+                    else {
+                        continue;
+                    };
+                    decimal = Decimal {
+                        precision: Some(precision),
+                        scale,
+                    };
+                    end += 5;
+                }
+                _ => continue,
+            }
+        }
+        while text(end).is_some_and(|t| {
+            ["UNSIGNED", "SIGNED", "ZEROFILL"]
+                .iter()
+                .any(|k| t.eq_ignore_ascii_case(k))
+        }) {
+            end += 1;
+        }
+        let range = t.start..tokens[end - 1].end;
+        let padding = blank(&schema[range.start + 6..range.end]);
+        out.replace_range(range, &format!("FLOAT8{padding}"));
+        found.push((name_end, Found::Decimal(decimal)));
+    }
+    let keys = keys.into_iter().map(|(_, key)| key).collect();
+    (out, found, enums, keys)
+}
+
+/// Give the columns of unknown types in schemas, parsed from [parsable], their own type
+///
+/// SQLite has no decimal type, so decimal columns are left as floats there. PostgreSQL
+/// `uuid` columns are typed as text, so that they can be compared with text literals.
+pub(crate) fn mark(schemas: &mut Schemas<'static>, dialect: &SQLDialect) {
+    // Each type or annotation belongs to the last column named before it
+    let mut ends: Vec<usize> = schemas
+        .schemas
+        .values()
+        .flat_map(|s| &s.columns)
+        .map(|c| c.identifier.span.end)
+        .collect();
+    ends.sort_unstable();
+    let owner = |at: usize| ends.iter().rev().find(|e| **e <= at).copied();
+
+    let mut columns = Vec::new();
+    let mut give = |t: &mut Type<'static>, base: BaseType, custom: Custom| {
+        let args = Arc::new(Vec::new());
+        columns.push((Arc::as_ptr(&args) as usize, custom));
+        *t = Type::Args(base, args);
+    };
+    for schema in schemas.schemas.values_mut() {
+        for column in &mut schema.columns {
+            let end = column.identifier.span.end;
+            let found: Vec<Found> = PARSABLE
+                .1
+                .iter()
+                .filter(|(at, _)| owner(*at) == Some(end))
+                .map(|(_, f)| f.clone())
+                .collect();
+            let mut element = FullType {
+                t: column.type_.t.clone(),
+                not_null: true,
+                list_hack: false,
+            };
+            for f in &found {
+                match (f, dialect) {
+                    (Found::Decimal(_), SQLDialect::Sqlite) => (),
+                    (Found::Decimal(decimal), _) => {
+                        give(&mut element.t, BaseType::Float, Custom::Decimal(*decimal))
+                    }
+                    (Found::PgUuid, _) => give(&mut element.t, BaseType::String, Custom::PgUuid),
+                    (Found::Uuid, _) => {
+                        let base = element.t.base();
+                        give(&mut element.t, base, Custom::Uuid)
+                    }
+                    (Found::NullableElements, _) => element.not_null = false,
+                    (Found::Json, _) => give(&mut element.t, BaseType::String, Custom::Json),
+                    (Found::Interval, _) => {
+                        give(&mut element.t, BaseType::TimeInterval, Custom::Interval)
+                    }
+                    (Found::Inet, _) => give(&mut element.t, BaseType::Any, Custom::Inet),
+                    (Found::Enum(e), _) => give(&mut element.t, BaseType::String, Custom::Enum(*e)),
+                    (Found::Type(t), _) => element.t = t.clone(),
+                    // SQLite does not enforce the length of text
+                    (Found::Length(_), SQLDialect::Sqlite) => (),
+                    (Found::Length(length), _) => {
+                        if element.t == Type::Base(BaseType::String) {
+                            give(&mut element.t, BaseType::String, Custom::Varchar(*length))
+                        }
+                    }
+                    (Found::Array, _) => (),
+                }
+            }
+            if found.contains(&Found::Array) {
+                let base = element.t.base();
+                give(&mut column.type_.t, base, Custom::Array(element));
+            } else {
+                column.type_.t = element.t;
+            }
+        }
+    }
+    let _ = COLUMNS.set(columns);
+}
+
+/// What t stands for if it is the type of a column of an unknown type
+pub(crate) fn custom(t: &Type<'_>) -> Option<&'static Custom> {
+    let Type::Args(_, args) = t else {
+        return None;
+    };
+    let address = Arc::as_ptr(args) as usize;
+    COLUMNS
+        .get()?
+        .iter()
+        .find(|(a, _)| *a == address)
+        .map(|(_, c)| c)
+}
+
+/// Name the types of columns of unknown types in an issue message by their base type
+pub(crate) fn rename(message: &str) -> String {
+    let mut message = message.to_string();
+    for base in [
+        BaseType::Any,
+        BaseType::Bool,
+        BaseType::Bytes,
+        BaseType::Date,
+        BaseType::DateTime,
+        BaseType::Float,
+        BaseType::Integer,
+        BaseType::String,
+        BaseType::Time,
+        BaseType::TimeStamp,
+        BaseType::TimeInterval,
+    ] {
+        message = message.replace(&format!("args({base})"), &base.to_string());
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace() {
+        let schema = "CREATE TABLE t (\n  `a` DECIMAL(15,\n 2) UNSIGNED NOT NULL,\n  \
+                      numeric NUMERIC,\n  c INT\n);";
+        let (out, customs, _, _) = replace(schema, &SQLDialect::MariaDB);
+        assert_eq!(
+            out,
+            "CREATE TABLE t (\n  `a` FLOAT8     \n             NOT NULL,\n  \
+             numeric FLOAT8 ,\n  c INT\n);"
+        );
+        assert_eq!(out.len(), schema.len());
+        assert_eq!(
+            customs,
+            [
+                (
+                    22,
+                    Found::Decimal(Decimal {
+                        precision: Some(15),
+                        scale: 2
+                    })
+                ),
+                (
+                    67,
+                    Found::Decimal(Decimal {
+                        precision: None,
+                        scale: 0
+                    })
+                )
+            ]
+        );
+
+        let schema = "CREATE TABLE t (\n  a UUID,\n  `b` BINARY(16), -- sqlx-type: uuid\n  \
+                      c CHAR(36) /* sqlx-type: uuid */\n);\n-- sqlx-type: uuid";
+        let (out, customs, _, _) = replace(schema, &SQLDialect::MariaDB);
+        assert_eq!(out, schema.replace("UUID,", "CHAR,"));
+        assert_eq!(
+            customs,
+            [
+                (20, Found::Uuid),
+                (32, Found::Uuid),
+                (67, Found::Length(36)),
+                (67, Found::Uuid)
+            ]
+        );
+
+        let schema = "CREATE TABLE t (id UUID DEFAULT UUID(), uuid INT);\n\
+                      ALTER TABLE t MODIFY uuid BINARY(16) NOT NULL;";
+        let (out, customs, _, _) = replace(schema, &SQLDialect::MariaDB);
+        assert_eq!(out, schema.replacen("UUID", "CHAR", 1));
+        assert_eq!(customs, [(18, Found::Uuid)]);
+
+        let schema =
+            "CREATE TABLE t (a integer[] NOT NULL,\nb uuid[] -- sqlx-type: nullable elements\n)";
+        let (out, found, _, _) = replace(schema, &SQLDialect::PostgreSQL);
+        assert_eq!(
+            out,
+            "CREATE TABLE t (a INT       NOT NULL,\nb CHAR   -- sqlx-type: nullable elements\n)"
+        );
+        assert_eq!(
+            found,
+            [
+                (17, Found::Type(Type::I32)),
+                (25, Found::Array),
+                (39, Found::PgUuid),
+                (44, Found::Array),
+                (39, Found::NullableElements)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_keys() {
+        let schema = "CREATE TABLE `T` (\n  PRIMARY KEY (`id`),\n  id INT,\n  a CHAR(3),\n  \
+                      b INT,\n  UNIQUE KEY uuid (a(2), `b`) -- pair\n);\n\
+                      ALTER TABLE `T` ADD UNIQUE KEY (b);";
+        let (out, _, _, keys) = replace(schema, &SQLDialect::MariaDB);
+        assert_eq!(
+            out,
+            "CREATE TABLE `T` (\n                     \n  id INT,\n  a CHAR(3),\n  \
+             b INT \n                                     \n);\n\
+             ALTER TABLE `T` ADD UNIQUE KEY (b);"
+        );
+        assert_eq!(
+            keys,
+            [
+                ("t".to_string(), vec!["id".to_string()]),
+                ("t".to_string(), vec!["a".to_string(), "b".to_string()])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_postgres() {
+        let schema = "CREATE TYPE mood AS ENUM ('sad', 'it''s, ok');\n\
+                      CREATE TABLE t (a timestamp NOT NULL, b timestamp(3) without time zone,\n\
+                      c timestamp with time zone, d jsonb, e inet, f interval, g mood[]);";
+        let (out, found, enums, _) = replace(schema, &SQLDialect::PostgreSQL);
+        assert_eq!(
+            out,
+            "                                              \n\
+             CREATE TABLE t (a DATETIME  NOT NULL, b DATETIME (3)                  ,\n\
+             c timestamp with time zone, d jsonb, e inet, f interval, g mood  );"
+        );
+        assert_eq!(
+            enums,
+            [Enum {
+                name: "mood".to_string(),
+                values: vec!["sad".to_string(), "it's, ok".to_string()]
+            }]
+        );
+        assert_eq!(
+            found,
+            [
+                (148, Found::Json),
+                (157, Found::Inet),
+                (165, Found::Interval),
+                (177, Found::Enum(0)),
+                (182, Found::Array)
+            ]
+        );
+
+        let schema = "CREATE TABLE u (a serial, b int8, c SmallSerial, d bigint)";
+        let (out, found, _, _) = replace(schema, &SQLDialect::PostgreSQL);
+        assert_eq!(
+            out,
+            "CREATE TABLE u (a INT   , b INT , c INT        , d bigint)"
+        );
+        assert_eq!(
+            found,
+            [
+                (17, Found::Type(Type::I32)),
+                (27, Found::Type(Type::I64)),
+                (35, Found::Type(Type::I16))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_sqlite() {
+        let schema = "CREATE TABLE t (\n  a UNSIGNED BIG INT NOT NULL,\n  \"b\" NVARCHAR(20),\n  \
+                      -- c\n  c ANY, d CLOB, e INT, f NUMBER(3)\n) WITHOUT ROWID;\n\
+                      CREATE TABLE s (a INT, b ANY, PRIMARY KEY (a)) STRICT, WITHOUT ROWID;";
+        let (out, found, _, keys) = replace(schema, &SQLDialect::Sqlite);
+        assert_eq!(
+            out,
+            "CREATE TABLE t (\n  a INT              NOT NULL,\n  \"b\" TEXT        ,\n  \
+             -- c\n  c INT, d TEXT, e INT, f INT      \n)              ;\n\
+             CREATE TABLE s (a INT, b INT                 ) STRICT               ;"
+        );
+        assert_eq!(keys, [("s".to_string(), vec!["a".to_string()])]);
+        assert_eq!(
+            found,
+            [
+                (20, Found::Type(Type::Base(BaseType::Integer))),
+                (53, Found::Type(Type::Base(BaseType::String))),
+                (78, Found::Type(Type::Base(BaseType::Any))),
+                (85, Found::Type(Type::Base(BaseType::String))),
+                (100, Found::Type(Type::Base(BaseType::Any))),
+                (145, Found::Type(Type::Base(BaseType::Integer))),
+                (152, Found::Type(Type::Base(BaseType::Any))),
+            ]
+        );
+    }
+}
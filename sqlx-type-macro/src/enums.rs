@@ -0,0 +1,91 @@
+//! Rust enums for PostgreSQL enum types
+//!
+//! `sql_enum!(pub enum Mood)` defines an enum with a variant for each value of the enum type
+//! `mood` of the schema, the type whose name in camel case is the name of the enum. Columns
+//! of the type are returned as the enum, which must be in scope where they are queried.
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::Ident;
+
+use crate::custom;
+
+/// Define the enum name for the enum type of the schema with the same name in camel case
+pub(crate) fn quote_sql_enum(
+    attrs: &[syn::Attribute],
+    vis: &syn::Visibility,
+    name: &Ident,
+) -> TokenStream {
+    let Some(e) = custom::enums()
+        .iter()
+        .find(|e| *name == custom::camel_case(&e.name))
+    else {
+        return syn::Error::new(
+            name.span(),
+            format!("No enum type named {name} in camel case in the schema"),
+        )
+        .to_compile_error();
+    };
+    let type_name = &e.name;
+    let values = &e.values;
+    let variants: Vec<Ident> = values
+        .iter()
+        .map(|v| format_ident!("{}", custom::camel_case(v)))
+        .collect();
+    let invalid = format!("invalid value {{:?}} for enum type {type_name}");
+    quote! {
+        #(#attrs)*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        #vis enum #name {
+            #(#variants,)*
+        }
+
+        impl #name {
+            /// The value of the enum type in the database
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    #(Self::#variants => #values,)*
+                }
+            }
+        }
+
+        impl ::sqlx::Type<::sqlx::postgres::Postgres> for #name {
+            fn type_info() -> ::sqlx::postgres::PgTypeInfo {
+                ::sqlx::postgres::PgTypeInfo::with_name(#type_name)
+            }
+        }
+
+        impl ::sqlx::postgres::PgHasArrayType for #name {
+            fn array_type_info() -> ::sqlx::postgres::PgTypeInfo {
+                ::sqlx::postgres::PgTypeInfo::array_of(#type_name)
+            }
+        }
+
+        impl<'q> ::sqlx::Encode<'q, ::sqlx::postgres::Postgres> for #name {
+            fn encode_by_ref(
+                &self,
+                buf: &mut ::sqlx::postgres::PgArgumentBuffer,
+            ) -> ::std::result::Result<::sqlx::encode::IsNull, ::sqlx::error::BoxDynError> {
+                <&str as ::sqlx::Encode<'q, ::sqlx::postgres::Postgres>>::encode(self.as_str(), buf)
+            }
+        }
+
+        impl<'r> ::sqlx::Decode<'r, ::sqlx::postgres::Postgres> for #name {
+            fn decode(
+                value: ::sqlx::postgres::PgValueRef<'r>,
+            ) -> ::std::result::Result<Self, ::sqlx::error::BoxDynError> {
+                match <&str as ::sqlx::Decode<'r, ::sqlx::postgres::Postgres>>::decode(value)? {
+                    #(#values => ::std::result::Result::Ok(Self::#variants),)*
+                    v => ::std::result::Result::Err(::std::format!(#invalid, v).into()),
+                }
+            }
+        }
+
+        // The enum is accepted for the columns of the type, and for comparing with them
+        impl sqlx_type::SqlEnum for #name {}
+        impl sqlx_type::ArgIn<#name> for #name {}
+        impl sqlx_type::ArgIn<Option<#name>> for #name {}
+        impl sqlx_type::ArgIn<&str> for #name {}
+        impl sqlx_type::ArgIn<Option<&str>> for #name {}
+        impl<const IDX: usize> sqlx_type::ArgOut<#name, IDX> for #name {}
+    }
+}
@@ -0,0 +1,312 @@
+//! Optional fragments of queries
+//!
+//! A fragment is a part of a query in square brackets containing arguments, such as
+//! `[AND status = ?status]`. It is only included in the query when all arguments in it are
+//! `Some`. Arguments can be named as `?name`, which refers to an argument given as
+//! `name = expr`, or to a variable in scope.
+use proc_macro2::Span;
+use sql_type::SQLDialect;
+use syn::{Expr, Ident};
+
+use crate::lists::{tokenize, Kind, Token};
+
+/// The largest number of fragments in a query, since every combination is typed
+pub(crate) const MAX_FRAGMENTS: usize = 10;
+
+/// An optional fragment of a query
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Fragment {
+    /// Start of the fragment in the query, at the opening bracket
+    pub start: usize,
+    /// End of the fragment in the query, after the closing bracket
+    pub end: usize,
+    /// The arguments in the fragment
+    pub args: Vec<usize>,
+}
+
+/// A query with named arguments replaced by placeholders, and the optional fragments in it
+pub(crate) struct FragmentQuery {
+    /// The query with the brackets of fragments replaced by spaces
+    pub query: String,
+    /// The arguments of the query in order
+    pub args: Vec<Expr>,
+    /// The fragments in order
+    pub fragments: Vec<Fragment>,
+}
+
+/// Return the name of the named argument starting at token `i`
+fn argument_name<'a>(query: &'a str, tokens: &[Token], i: usize) -> Option<&'a str> {
+    let (t, n) = (tokens.get(i)?, tokens.get(i + 1)?);
+    (t.kind == Kind::Question && n.kind == Kind::Word && t.end == n.start)
+        .then(|| &query[n.start..n.end])
+}
+
+/// The names of the named arguments used in query
+pub(crate) fn argument_names(query: &str) -> Vec<String> {
+    let tokens = tokenize(query);
+    let mut names: Vec<String> = Vec::new();
+    for i in 0..tokens.len() {
+        if let Some(name) = argument_name(query, &tokens, i) {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+impl FragmentQuery {
+    /// Resolve the named arguments and find the fragments of query
+    ///
+    /// Named arguments not found in `named` refer to variables in scope
+    pub(crate) fn new(
+        query: &str,
+        positional: Vec<Expr>,
+        named: &[(Ident, Expr)],
+        span: Span,
+        dialect: &SQLDialect,
+    ) -> syn::Result<Self> {
+        let dollar = matches!(dialect, SQLDialect::PostgreSQL);
+        let tokens = tokenize(query);
+
+        // Find brackets containing arguments. With PostgreSQL brackets directly after an
+        // expression or ARRAY are subscripts and array constructors
+        let mut stack = Vec::new();
+        let mut brackets = Vec::new();
+        for (i, t) in tokens.iter().enumerate() {
+            match t.kind {
+                Kind::OpenBracket => {
+                    let array = dollar
+                        && i != 0
+                        && (tokens[i - 1].end == t.start
+                            || query[tokens[i - 1].start..tokens[i - 1].end]
+                                .eq_ignore_ascii_case("ARRAY"));
+                    stack.push((i, array));
+                }
+                Kind::CloseBracket => {
+                    if let Some((open, false)) = stack.pop() {
+                        let has_args = tokens[open..i].iter().any(|t| {
+                            matches!(t.kind, Kind::Question | Kind::Dollar(_) | Kind::List)
+                        });
+                        if has_args {
+                            brackets.push((open, i));
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        brackets.sort_unstable();
+        if brackets.windows(2).any(|w| w[1].0 < w[0].1) {
+            return Err(syn::Error::new(
+                span,
+                "Optional fragments can not be nested",
+            ));
+        }
+        if brackets.len() > MAX_FRAGMENTS {
+            return Err(syn::Error::new(
+                span,
+                format!("At most {MAX_FRAGMENTS} optional fragments are supported"),
+            ));
+        }
+        let fragment = |i: usize| brackets.iter().position(|(o, c)| *o < i && i < *c);
+
+        // Rewrite the query, numbering the placeholders in order
+        let mut out = String::with_capacity(query.len());
+        let mut args = Vec::new();
+        let mut positional = positional.into_iter();
+        let mut fragments: Vec<Fragment> = brackets
+            .iter()
+            .map(|_| Fragment {
+                start: 0,
+                end: 0,
+                args: Vec::new(),
+            })
+            .collect();
+        let mut outside = Vec::new();
+        let mut pos = 0;
+        let mut i = 0;
+        while let Some(t) = tokens.get(i) {
+            let f = fragment(i);
+            if let Some(name) = argument_name(query, &tokens, i) {
+                if dollar {
+                    return Err(syn::Error::new(
+                        span,
+                        "Named arguments are not supported with PostgreSQL, use $n",
+                    ));
+                }
+                // Such as the numbered arguments ?1 of SQLite
+                let Ok(mut ident) = syn::parse_str::<Ident>(name) else {
+                    return Err(syn::Error::new(
+                        span,
+                        format!("Invalid named argument ?{name}, names must be Rust identifiers"),
+                    ));
+                };
+                ident.set_span(span);
+                out.push_str(&query[pos..t.start]);
+                out.push('?');
+                pos = tokens[i + 1].end;
+                let arg = match named.iter().find(|(n, _)| n == name) {
+                    Some((_, e)) => e.clone(),
+                    None => syn::parse_quote!(#ident),
+                };
+                if let Some(f) = f {
+                    fragments[f].args.push(args.len());
+                }
+                args.push(arg);
+                i += 2;
+                continue;
+            }
+            match t.kind {
+                Kind::List | Kind::Order if f.is_some() => {
+                    return Err(syn::Error::new(
+                        span,
+                        "_LIST_ and _ORDER_ are not supported in optional fragments",
+                    ));
+                }
+                Kind::Question | Kind::List | Kind::Order if !dollar => {
+                    if let Some(f) = f {
+                        fragments[f].args.push(args.len());
+                    }
+                    args.extend(positional.next());
+                }
+                Kind::Dollar(n) if dollar && n != 0 => match f {
+                    Some(f) => fragments[f].args.push(n - 1),
+                    None => outside.push(n - 1),
+                },
+                Kind::OpenBracket | Kind::CloseBracket => {
+                    if let Some(f) = brackets.iter().position(|(o, c)| *o == i || *c == i) {
+                        out.push_str(&query[pos..t.start]);
+                        if t.kind == Kind::OpenBracket {
+                            fragments[f].start = out.len();
+                        } else {
+                            fragments[f].end = out.len() + 1;
+                        }
+                        out.push(' ');
+                        pos = t.end;
+                    }
+                }
+                _ => (),
+            }
+            i += 1;
+        }
+        out.push_str(&query[pos..]);
+        args.extend(positional);
+
+        if dollar {
+            for (j, f) in fragments.iter().enumerate() {
+                for a in &f.args {
+                    let in_other = fragments
+                        .iter()
+                        .enumerate()
+                        .any(|(k, o)| k != j && o.args.contains(a));
+                    if outside.contains(a) || in_other {
+                        return Err(syn::Error::new(
+                            span,
+                            format!(
+                                "${} is used in an optional fragment and elsewhere in the query",
+                                a + 1
+                            ),
+                        ));
+                    }
+                }
+            }
+            for f in &mut fragments {
+                f.args.sort_unstable();
+                f.args.dedup();
+            }
+        }
+        Ok(Self {
+            query: out,
+            args,
+            fragments,
+        })
+    }
+}
+
+/// The query with the fragments not in the bit set `enabled` replaced by spaces
+pub(crate) fn combination(query: &str, fragments: &[Fragment], enabled: usize) -> String {
+    let mut query = query.to_string();
+    for (i, f) in fragments.iter().enumerate() {
+        if enabled & (1 << i) == 0 {
+            query.replace_range(f.start..f.end, &" ".repeat(f.end - f.start));
+        }
+    }
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment_query(query: &str, args: usize, dialect: &SQLDialect) -> FragmentQuery {
+        let args = (0..args)
+            .map(|i| syn::parse_str(&format!("a{i}")).unwrap())
+            .collect();
+        let named = [(
+            Ident::new("s", Span::call_site()),
+            syn::parse_str("status").unwrap(),
+        )];
+        FragmentQuery::new(query, args, &named, Span::call_site(), dialect).unwrap()
+    }
+
+    #[test]
+    fn test_fragments() {
+        let q = fragment_query(
+            "SELECT a FROM t WHERE b=? [AND c = ?s] [AND (d > ? OR e < ?since)] AND f=?",
+            2,
+            &SQLDialect::MariaDB,
+        );
+        assert_eq!(
+            q.query,
+            "SELECT a FROM t WHERE b=?  AND c = ?   AND (d > ? OR e < ?)  AND f=?"
+        );
+        let args: Vec<_> = q
+            .args
+            .iter()
+            .map(|a| quote::quote!(#a).to_string())
+            .collect();
+        assert_eq!(args, ["a0", "status", "a1", "since"]);
+        assert_eq!(
+            q.fragments,
+            vec![
+                Fragment {
+                    start: 26,
+                    end: 37,
+                    args: vec![1]
+                },
+                Fragment {
+                    start: 38,
+                    end: 60,
+                    args: vec![2, 3]
+                }
+            ]
+        );
+        assert_eq!(
+            combination(&q.query, &q.fragments, 2),
+            "SELECT a FROM t WHERE b=?              AND (d > ? OR e < ?)  AND f=?"
+        );
+        assert_eq!(argument_names("SELECT ?a, '?b', ? c, ?a"), ["a"]);
+
+        let q = fragment_query(
+            "SELECT a[$1] FROM t WHERE b = $1 [AND c = ANY(ARRAY[$2, $2])] [AND d = $3]",
+            0,
+            &SQLDialect::PostgreSQL,
+        );
+        assert_eq!(
+            q.query,
+            "SELECT a[$1] FROM t WHERE b = $1  AND c = ANY(ARRAY[$2, $2])   AND d = $3 "
+        );
+        let args: Vec<_> = q.fragments.iter().map(|f| f.args.clone()).collect();
+        assert_eq!(args, [vec![1], vec![2]]);
+
+        let q = fragment_query("SELECT [a b] FROM t", 0, &SQLDialect::Sqlite);
+        assert_eq!(q.query, "SELECT [a b] FROM t");
+        assert!(q.fragments.is_empty());
+
+        for query in ["SELECT ?1", "SELECT ?type"] {
+            let q = FragmentQuery::new(query, vec![], &[], Span::call_site(), &SQLDialect::Sqlite);
+            assert!(q.is_err());
+        }
+    }
+}
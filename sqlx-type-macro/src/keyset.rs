@@ -0,0 +1,210 @@
+//! Keyset pagination
+//!
+//! The page after a row of a query ordered by `a, b` is found by adding `(a, b) > (?, ?)` to
+//! the WHERE clause, with `<` for descending order. The sort key of the row is given by a
+//! cursor struct with a member for each key.
+use proc_macro2::Span;
+use sql_parse::{Expression, IdentifierPart, OptSpanned, Spanned};
+use sql_type::SQLDialect;
+use syn::{Expr, Ident};
+
+use crate::fragments::Fragment;
+use crate::lists::{tokenize, Kind, ListQuery};
+
+/// The query of the page after a cursor
+pub(crate) struct NextPage {
+    /// The names of the columns of the ORDER BY, in order
+    pub keys: Vec<String>,
+    /// The query with the keyset predicate added
+    pub query: String,
+    /// The arguments of the query, including the members of the cursor
+    pub args: Vec<Expr>,
+    /// The optional fragments of the query
+    pub fragments: Vec<Fragment>,
+}
+
+/// Add the keyset predicate comparing the keys of the ORDER BY with the members of `cursor`
+/// to query
+pub(crate) fn next_page(
+    query: &str,
+    args: &[Expr],
+    fragments: &[Fragment],
+    cursor: &Ident,
+    span: Span,
+    dialect: &SQLDialect,
+) -> syn::Result<NextPage> {
+    let error = |msg: &str| syn::Error::new(span, msg);
+    let tokens = tokenize(query);
+    if tokens.iter().any(|t| t.kind == Kind::Order) {
+        return Err(error("_ORDER_ can not be used with keyset pagination"));
+    }
+    let parsable = ListQuery::new(query, dialect).parsable;
+    let options = crate::parse_options(dialect);
+    let mut issues = sql_parse::Issues::new(&parsable);
+    let Some(sql_parse::Statement::Select(select)) =
+        sql_parse::parse_statement(&parsable, &mut issues, &options)
+    else {
+        return Err(error("Keyset pagination requires a SELECT query"));
+    };
+    if select.group_by.is_some() || select.having.is_some() || select.window_span.is_some() {
+        return Err(error(
+            "Keyset pagination is not supported with GROUP BY, HAVING or WINDOW",
+        ));
+    }
+    let Some((order_span, order)) = &select.order_by else {
+        return Err(error("Keyset pagination requires an ORDER BY"));
+    };
+
+    // The keys must be columns with the same direction
+    let mut keys = Vec::new();
+    let mut exprs = Vec::new();
+    let mut desc = None;
+    for (e, flag) in order {
+        let Expression::Identifier(parts) = e else {
+            return Err(error("The keys of the ORDER BY must be columns"));
+        };
+        let Some(IdentifierPart::Name(name)) = parts.last() else {
+            return Err(error("The keys of the ORDER BY must be columns"));
+        };
+        let d = flag
+            .opt_span()
+            .is_some_and(|s| query[s.start..s.end].eq_ignore_ascii_case("DESC"));
+        if *desc.get_or_insert(d) != d {
+            return Err(error(
+                "The keys of the ORDER BY must all be ascending or all be descending",
+            ));
+        }
+        let s = e.span();
+        keys.push(name.value.to_string());
+        exprs.push(&query[s.start..s.end]);
+    }
+
+    // Add the predicate at the end of the WHERE clause, at the ORDER BY
+    let at = order_span.start;
+    let placeholders: Vec<String> = match dialect {
+        SQLDialect::PostgreSQL => (0..keys.len())
+            .map(|i| format!("${}", args.len() + i + 1))
+            .collect(),
+        _ => vec!["?".to_string(); keys.len()],
+    };
+    let op = if desc == Some(true) { "<" } else { ">" };
+    let predicate = match (exprs.as_slice(), placeholders.as_slice()) {
+        ([e], [p]) => format!("{e} {op} {p}"),
+        _ => format!("({}) {op} ({})", exprs.join(", "), placeholders.join(", ")),
+    };
+    let mut inserts = Vec::new();
+    match &select.where_ {
+        Some((_, where_span)) => {
+            inserts.push((where_span.end, " (".to_string()));
+            inserts.push((at, format!(") AND {predicate} ")));
+        }
+        None => inserts.push((at, format!("WHERE {predicate} "))),
+    }
+    let mut next = query.to_string();
+    for (p, s) in inserts.iter().rev() {
+        next.insert_str(*p, s);
+    }
+    let shift = |p: usize, inclusive: bool| -> usize {
+        p + inserts
+            .iter()
+            .filter(|(at, _)| *at < p || (inclusive && *at == p))
+            .map(|(_, s)| s.len())
+            .sum::<usize>()
+    };
+
+    // The members of the cursor are bound after the arguments before the ORDER BY
+    let index = match dialect {
+        SQLDialect::PostgreSQL => args.len(),
+        _ => tokens
+            .iter()
+            .filter(|t| t.start < at && matches!(t.kind, Kind::Question | Kind::List))
+            .count(),
+    };
+    let mut members = Vec::new();
+    for key in &keys {
+        let Ok(member) = syn::parse_str::<Ident>(&format!("r#{key}")) else {
+            return Err(error(&format!("Column {key} can not be used as a member")));
+        };
+        members.push(syn::parse_quote!(#cursor.#member));
+    }
+    let mut next_args = args.to_vec();
+    next_args.splice(index..index, members);
+    let next_fragments = fragments
+        .iter()
+        .map(|f| Fragment {
+            start: shift(f.start, true),
+            end: shift(f.end, false),
+            args: match dialect {
+                SQLDialect::PostgreSQL => f.args.clone(),
+                _ => f
+                    .args
+                    .iter()
+                    .map(|a| if *a >= index { a + keys.len() } else { *a })
+                    .collect(),
+            },
+        })
+        .collect();
+    Ok(NextPage {
+        keys,
+        query: next,
+        args: next_args,
+        fragments: next_fragments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next(query: &str, args: usize, fragments: &[Fragment], dialect: &SQLDialect) -> NextPage {
+        let args: Vec<Expr> = (0..args)
+            .map(|i| syn::parse_str(&format!("a{i}")).unwrap())
+            .collect();
+        let cursor = Ident::new("cursor", Span::call_site());
+        next_page(query, &args, fragments, &cursor, Span::call_site(), dialect).unwrap()
+    }
+
+    #[test]
+    fn test_next_page() {
+        let q = next(
+            "SELECT `a`, `b` FROM `t` WHERE c = ?  AND d = ?  ORDER BY `t`.`a`, b LIMIT ?",
+            3,
+            &[Fragment {
+                start: 37,
+                end: 48,
+                args: vec![1],
+            }],
+            &SQLDialect::MariaDB,
+        );
+        assert_eq!(q.keys, ["a", "b"]);
+        assert_eq!(
+            q.query,
+            "SELECT `a`, `b` FROM `t` WHERE ( c = ?  AND d = ?  ) AND (`t`.`a`, b) > (?, ?) ORDER BY `t`.`a`, b LIMIT ?"
+        );
+        let args: Vec<_> = q
+            .args
+            .iter()
+            .map(|a| quote::quote!(#a).to_string())
+            .collect();
+        assert_eq!(args, ["a0", "a1", "cursor . r#a", "cursor . r#b", "a2"]);
+        assert_eq!(
+            q.fragments,
+            vec![Fragment {
+                start: 39,
+                end: 50,
+                args: vec![1]
+            }]
+        );
+
+        let q = next(
+            "SELECT a FROM t ORDER BY a DESC LIMIT $1",
+            1,
+            &[],
+            &SQLDialect::PostgreSQL,
+        );
+        assert_eq!(
+            q.query,
+            "SELECT a FROM t WHERE a < $2 ORDER BY a DESC LIMIT $1"
+        );
+    }
+}
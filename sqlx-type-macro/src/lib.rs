@@ -1,5 +1,6 @@
 #![forbid(unsafe_code)]
 
+use std::collections::BTreeMap;
 use std::ops::Deref;
 use std::path::PathBuf;
 
@@ -11,7 +12,20 @@ use quote::{format_ident, quote, quote_spanned};
 use sql_type::schema::{parse_schemas, Schemas};
 use sql_type::{type_statement, Issue, SQLArguments, SQLDialect, SelectTypeColumn, TypeOptions};
 use syn::spanned::Spanned;
-use syn::{parse::Parse, punctuated::Punctuated, Expr, Ident, LitStr, Token};
+use syn::{parse::Parse, parse::Parser, punctuated::Punctuated, Expr, Ident, LitStr, Token};
+use types::{rust_type, Usage};
+
+mod ast;
+mod cardinality;
+mod custom;
+mod enums;
+mod fragments;
+mod keyset;
+mod lists;
+mod literals;
+mod paging;
+mod splice;
+mod types;
 
 static SCHEMA_PATH: Lazy<PathBuf> = Lazy::new(|| {
     let mut schema_path: PathBuf = std::env::var("CARGO_MANIFEST_DIR")
@@ -53,7 +67,7 @@ static SCHEMA_PATH: Lazy<PathBuf> = Lazy::new(|| {
 
 // If we are in a workspace, lookup `workspace_root` since `CARGO_MANIFEST_DIR` won't
 // reflect the workspace dir: https://github.com/rust-lang/cargo/issues/3946
-static SCHEMA_SRC: Lazy<String> =
+pub(crate) static SCHEMA_SRC: Lazy<String> =
     Lazy::new(|| match std::fs::read_to_string(SCHEMA_PATH.as_path()) {
         Ok(v) => v,
         Err(e) => panic!(
@@ -76,10 +90,11 @@ fn issue_to_report(issue: Issue) -> Report<'static, std::ops::Range<usize>> {
         Label::new(issue.span)
             .with_order(-1)
             .with_priority(-1)
-            .with_message(issue.message),
+            .with_message(custom::rename(&issue.message)),
     );
     for frag in issue.fragments {
-        builder = builder.with_label(Label::new(frag.span).with_message(frag.message));
+        builder =
+            builder.with_label(Label::new(frag.span).with_message(custom::rename(&frag.message)));
     }
     builder.finish()
 }
@@ -126,9 +141,9 @@ impl<'a> ariadne::Cache<()> for &NamedSource<'a> {
     }
 }
 
-static SCHEMAS: Lazy<(Schemas, SQLDialect)> = Lazy::new(|| {
-    let schema_src = SCHEMA_SRC.as_str();
-    let dialect = if let Some(first_line) = schema_src.lines().next() {
+/// The dialect of the schema, given by a `sql-product` comment on its first line
+fn dialect(schema_src: &str) -> SQLDialect {
+    if let Some(first_line) = schema_src.lines().next() {
         if first_line.contains("sql-product: postgres") {
             SQLDialect::PostgreSQL
         } else if first_line.contains("sql-product: sqlite") {
@@ -138,11 +153,18 @@ static SCHEMAS: Lazy<(Schemas, SQLDialect)> = Lazy::new(|| {
         }
     } else {
         SQLDialect::MariaDB
-    };
+    }
+}
+
+pub(crate) static SCHEMAS: Lazy<(Schemas, SQLDialect)> = Lazy::new(|| {
+    let schema_src = SCHEMA_SRC.as_str();
+    let dialect = dialect(schema_src);
 
     let options = TypeOptions::new().dialect(dialect.clone());
-    let mut issues = sql_type::Issues::new(schema_src);
-    let schemas = parse_schemas(schema_src, &mut issues, &options);
+    let parsable = custom::parsable();
+    let mut issues = sql_type::Issues::new(parsable);
+    let mut schemas = parse_schemas(parsable, &mut issues, &options);
+    custom::mark(&mut schemas, &dialect);
     if !issues.is_ok() {
         let source = NamedSource("sqlx-type-schema.sql", Source::from(schema_src));
         let mut err = false;
@@ -160,29 +182,116 @@ static SCHEMAS: Lazy<(Schemas, SQLDialect)> = Lazy::new(|| {
     (schemas, dialect)
 });
 
+/// A query and the arguments given for it
+struct QueryArgs<'a> {
+    query: &'a str,
+    args: &'a [Expr],
+    fragments: &'a [fragments::Fragment],
+    last_span: Span,
+}
+
+/// The value bound for the argument v
+fn quote_bind(v: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    quote!({
+        use sqlx_type::BindArg as _;
+        (&sqlx_type::Bind::new(#v)).bind()
+    })
+}
+
+/// The maximum number of arguments of a statement
+fn max_arguments(dialect: &SQLDialect) -> usize {
+    match dialect {
+        SQLDialect::MariaDB | SQLDialect::PostgreSQL => 65535,
+        SQLDialect::Sqlite => 32766,
+    }
+}
+
+/// The sqlx database of the dialect
+fn database(dialect: &SQLDialect) -> proc_macro2::TokenStream {
+    match dialect {
+        SQLDialect::MariaDB => quote!(sqlx::mysql::MySql),
+        SQLDialect::Sqlite => quote!(sqlx::sqlite::Sqlite),
+        SQLDialect::PostgreSQL => quote!(sqlx::postgres::Postgres),
+    }
+}
+
+/// The placeholders of arguments in the dialect
+fn arguments(dialect: &SQLDialect) -> SQLArguments {
+    match dialect {
+        SQLDialect::MariaDB | SQLDialect::Sqlite => SQLArguments::QuestionMark,
+        SQLDialect::PostgreSQL => SQLArguments::Dollar,
+    }
+}
+
+/// The options to type queries of the dialect, which may contain `_LIST_`
+fn type_options(dialect: &SQLDialect) -> TypeOptions {
+    TypeOptions::new()
+        .dialect(dialect.clone())
+        .arguments(arguments(dialect))
+        .list_hack(true)
+}
+
+/// The options to parse queries of the dialect, which may contain `_LIST_`
+pub(crate) fn parse_options(dialect: &SQLDialect) -> sql_parse::ParseOptions {
+    sql_parse::ParseOptions::new()
+        .dialect(dialect.clone())
+        .arguments(arguments(dialect))
+        .list_hack(true)
+}
 fn quote_args(
     errors: &mut Vec<proc_macro2::TokenStream>,
-    query: &str,
-    last_span: Span,
-    args: &[Expr],
+    query: QueryArgs<'_>,
     arguments: &[(sql_type::ArgumentKey<'_>, sql_type::FullType)],
+    columns: Option<&[SelectTypeColumn]>,
     dialect: &SQLDialect,
 ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
-    let cls = match dialect {
-        SQLDialect::MariaDB => quote!(sqlx::mysql::MySql),
-        SQLDialect::Sqlite => quote!(sqlx::sqlite::Sqlite),
-        SQLDialect::PostgreSQL => quote!(sqlx::postgres::Postgres),
-    };
+    let QueryArgs {
+        query,
+        args,
+        fragments,
+        last_span,
+    } = query;
+    let cls = database(dialect);
+
+    let lists = lists::ListQuery::new(query, dialect);
+    for n in &lists.collisions {
+        errors.push(
+            syn::Error::new(
+                last_span,
+                format!(
+                    "${n} is taken by a _LIST_ or _ORDER_, which follows the highest $n before \
+                     it, number the arguments after it from ${}",
+                    n + 1
+                ),
+            )
+            .to_compile_error(),
+        );
+    }
+    let max_arguments = max_arguments(dialect);
+    let limits = paging::limit_args(&lists.typed, dialect);
+    let limit_type = paging::limit_type(dialect);
 
-    let mut at = Vec::new();
+    // The types of each argument, or of each element of the rows of tuple lists
+    let mut at: Vec<Vec<&sql_type::FullType>> = Vec::new();
+    // The types literals are checked against where they differ from the argument type
+    let compared = literals::compared_columns(&lists.typed, dialect);
+    let mut checked = BTreeMap::new();
     let inv = sql_type::FullType::invalid();
     for (k, v) in arguments {
         match k {
             sql_type::ArgumentKey::Index(i) => {
-                while at.len() <= *i {
-                    at.push(&inv);
+                let v = if limits.contains(i) { &limit_type } else { v };
+                if let (sql_type::Type::Base(_), Some(c)) = (&v.t, compared.get(i)) {
+                    checked.insert(lists.typed_arg(*i), *c);
+                }
+                let (i, j) = lists.typed_arg(*i);
+                if at.len() <= i {
+                    at.resize(i + 1, Vec::new());
                 }
-                at[*i] = v;
+                if at[i].len() <= j {
+                    at[i].resize(j + 1, &inv);
+                }
+                at[i][j] = v;
             }
             sql_type::ArgumentKey::Identifier(_) => {
                 errors.push(
@@ -193,6 +302,23 @@ fn quote_args(
         }
     }
 
+    // The argument of an _ORDER_ is not typed
+    let (order, order_at) = match lists.orders.as_slice() {
+        [] => (None, None),
+        [(order, offset)] => {
+            if at.len() <= *order {
+                at.resize(order + 1, Vec::new());
+            }
+            (Some(*order), Some(*offset))
+        }
+        _ => {
+            errors.push(
+                syn::Error::new(last_span, "Only one _ORDER_ is supported").to_compile_error(),
+            );
+            (None, None)
+        }
+    };
+
     if at.len() > args.len() {
         errors.push(
             syn::Error::new(
@@ -214,82 +340,274 @@ fn quote_args(
         .collect::<Vec<_>>();
 
     let mut arg_bindings = Vec::new();
+    let mut fragment_counts = Vec::new();
     let mut arg_add = Vec::new();
 
     let mut list_lengths = Vec::new();
+    let mut list_widths = Vec::new();
+    let mut arg_lengths = Vec::new();
+    let mut arg_widths = Vec::new();
 
-    for ((qa, ta), name) in args.iter().zip(at).zip(&arg_names) {
-        let mut t = match ta.t {
-            sql_type::Type::U8 => quote! {u8},
-            sql_type::Type::I8 => quote! {i8},
-            sql_type::Type::U16 => quote! {u16},
-            sql_type::Type::I16 => quote! {i16},
-            sql_type::Type::U32 => quote! {u32},
-            sql_type::Type::I32 => quote! {i32},
-            sql_type::Type::U64 => quote! {u64},
-            sql_type::Type::I64 => quote! {i64},
-            sql_type::Type::Base(sql_type::BaseType::Any) => quote! {sqlx_type::Any},
-            sql_type::Type::Base(sql_type::BaseType::Bool) => quote! {bool},
-            sql_type::Type::Base(sql_type::BaseType::Bytes) => quote! {&[u8]},
-            sql_type::Type::Base(sql_type::BaseType::Date) => quote! {sqlx_type::Date},
-            sql_type::Type::Base(sql_type::BaseType::DateTime) => quote! {sqlx_type::DateTime},
-            sql_type::Type::Base(sql_type::BaseType::Float) => quote! {sqlx_type::Float},
-            sql_type::Type::Base(sql_type::BaseType::Integer) => quote! {sqlx_type::Integer},
-            sql_type::Type::Base(sql_type::BaseType::String) => quote! {&str},
-            sql_type::Type::Base(sql_type::BaseType::Time) => quote! {sqlx_type::Time},
-            sql_type::Type::Base(sql_type::BaseType::TimeInterval) => todo!("time_interval"),
-            sql_type::Type::Base(sql_type::BaseType::TimeStamp) => quote! {sqlx_type::Timestamp},
-            sql_type::Type::Null => todo!("null"),
-            sql_type::Type::Invalid => quote! {std::convert::Infallible},
-            sql_type::Type::Enum(_) => quote! {&str},
-            sql_type::Type::Set(_) => quote! {&str},
-            sql_type::Type::Args(_, _) => todo!("args"),
-            sql_type::Type::F32 => quote! {f32},
-            sql_type::Type::F64 => quote! {f64},
-            sql_type::Type::JSON => quote! {sqlx_type::Any},
-        };
-        if !ta.not_null {
-            t = quote! {Option<#t>}
+    // Arguments given to ANY($n) are typed as an element of the array
+    let scalar_type = |i: usize, ta: &[&sql_type::FullType]| {
+        let t = rust_type(ta.first().copied().unwrap_or(&inv), Usage::Arg);
+        if lists.arrays.contains(&i) {
+            quote!(sqlx_type::Array<#t>)
+        } else {
+            t
         }
-        let span = qa.span();
-        if ta.list_hack {
-            list_lengths.push(quote!(#name.len()));
+    };
+
+    for (i, ((qa, ta), name)) in args.iter().zip(at).zip(&arg_names).enumerate() {
+        // Errors point at the argument, while the generated names resolve at the call site,
+        // which differs from the argument when called through the macro of a sql_fragment!
+        let span = qa.span().resolved_at(Span::call_site());
+        let checked: Vec<_> = (0..ta.len())
+            .map(|j| checked.get(&(i, j)).copied().unwrap_or(ta[j]))
+            .collect();
+        let error_count = errors.len();
+        literals::check_literals(errors, qa, &checked);
+        // A literal out of range is not also given the type it does not fit
+        let literal_fits = errors.len() == error_count;
+        if let Some((_, width)) = lists.lists.iter().find(|(a, _)| *a == i) {
+            let size = format_ident!("size{}", i);
+            list_lengths.push(quote!(#size));
+            list_widths.push(quote!(#width));
+            arg_lengths.push(quote!(#size));
+            arg_widths.push(quote!(#width));
+            let (t, add) = if *width == 1 {
+                let t = rust_type(ta.first().copied().unwrap_or(&inv), Usage::Arg);
+                let v = quote_bind(quote!(v));
+                (t, quote!(query_args.add(#v)))
+            } else {
+                let t =
+                    (0..*width).map(|j| rust_type(ta.get(j).copied().unwrap_or(&inv), Usage::Arg));
+                (
+                    quote!((#(#t),*)),
+                    quote!(sqlx_type::ListTuple::<#cls>::add_to(v, &mut query_args)),
+                )
+            };
             arg_bindings.push(quote_spanned! {span=>
                 let #name = &(#qa);
-                args_count += #name.len();
-                for v in #name.iter() {
-                    size_hints += ::sqlx::encode::Encode::<#cls>::size_hint(v);
-                }
+                let #size = sqlx_type::list_bucket(
+                    sqlx_type::ListArg::list_len(#name),
+                    #width,
+                    #max_arguments,
+                );
+                args_count += #size * #width;
                 if false {
                     sqlx_type::check_arg_list_hack::<#t, _>(#name);
                     ::std::panic!();
                 }
             });
+            // Pad the list to the bucket size by repeating the last element
+            arg_add.push(quote!(
+                let mut last = None;
+                for v in sqlx_type::ListArg::list_iter(#name) {
+                    if let Some(v) = last.replace(v) {
+                        e = e.and_then(|()| #add);
+                    }
+                }
+                if let Some(v) = last {
+                    for _ in sqlx_type::ListArg::list_len(#name)..#size {
+                        let v = ::std::clone::Clone::clone(&v);
+                        e = e.and_then(|()| #add);
+                    }
+                    e = e.and_then(|()| #add);
+                }
+            ));
+        } else if order == Some(i) {
+            arg_lengths.push(quote!(0));
+            arg_widths.push(quote!(1));
+            let check = match columns {
+                Some(columns) => paging::quote_order_check(name, columns),
+                None => {
+                    syn::Error::new(span, "_ORDER_ is only supported in queries returning rows")
+                        .to_compile_error()
+                }
+            };
+            arg_bindings.push(quote_spanned! {span=>
+                let #name: &sqlx_type::Order<_> = &(#qa);
+                #check
+            });
+        } else if let Some(k) = fragments.iter().position(|f| f.args.contains(&i)) {
+            // Arguments in optional fragments are only given when the fragment is active
+            let t = scalar_type(i, &ta);
+            arg_lengths.push(quote!((active[#k] as usize)));
+            arg_widths.push(quote!(1));
+            arg_bindings.push(quote_spanned! {span=>
+                let #name: &::std::option::Option<_> = &(#qa);
+                if false {
+                    if let ::std::option::Option::Some(v) = #name {
+                        sqlx_type::check_arg::<#t, _>(v);
+                    }
+                    ::std::panic!();
+                }
+            });
+            let v = quote_bind(quote!(v));
+            fragment_counts.push(quote!(
+                if let (true, ::std::option::Option::Some(v)) = (active[#k], #name) {
+                    args_count += 1;
+                    size_hints += ::sqlx::encode::Encode::<#cls>::size_hint(&#v);
+                }
+            ));
             arg_add.push(quote!(
-                for v in #name.iter() {
-                    e = e.and_then(|()| query_args.add(v));
+                if let (true, ::std::option::Option::Some(v)) = (active[#k], #name) {
+                    e = e.and_then(|()| query_args.add(#v));
                 }
             ));
         } else {
+            let t = scalar_type(i, &ta);
+            arg_lengths.push(quote!(1));
+            arg_widths.push(quote!(1));
+            // Unsuffixed integer literals are given the type of their integer column, and a
+            // bare None its option
+            let literal = match ta.first() {
+                Some(ft)
+                    if literal_fits
+                        && !lists.arrays.contains(&i)
+                        && literals::unsuffixed_integer(qa) =>
+                {
+                    types::literal_type(&ft.t)
+                }
+                Some(ft) if !lists.arrays.contains(&i) && literals::none(qa) => {
+                    types::literal_type(&ft.t).map(|t| quote!(::std::option::Option<#t>))
+                }
+                _ => None,
+            };
+            let binding = match literal {
+                Some(literal) => quote_spanned! {span=> let #name: &#literal = &(#qa);},
+                None => quote_spanned! {span=> let #name = &(#qa);},
+            };
+            let bound = quote_bind(quote!(#name));
             arg_bindings.push(quote_spanned! {span=>
-                let #name = &(#qa);
-                args_count += 1;
-                size_hints += ::sqlx::encode::Encode::<#cls>::size_hint(#name);
+                #binding
                 if false {
                     sqlx_type::check_arg::<#t, _>(#name);
                     ::std::panic!();
                 }
+                let #name = #bound;
+                args_count += 1;
+                size_hints += ::sqlx::encode::Encode::<#cls>::size_hint(&#name);
             });
             arg_add.push(quote!(e = e.and_then(|()| query_args.add(#name));));
         }
     }
 
-    let query = if list_lengths.is_empty() {
+    // The _ORDER_ is replaced first, at its offset in the runtime query, so the offsets after
+    // it are shifted by the difference in length of the ordered query
+    let query = &lists.runtime;
+    let shifted = |offset: usize| match order_at {
+        Some(at) if offset > at => quote!((#offset + ordered.len() - #query.len())),
+        _ => quote!(#offset),
+    };
+
+    // Make IN and NOT IN predicates of empty lists always false and true
+    let predicates = if list_lengths.is_empty() {
+        Vec::new()
+    } else {
+        lists.in_predicates(dialect)
+    };
+    let predicates: Vec<_> = predicates
+        .iter()
+        .map(|p| {
+            let list = match dialect {
+                SQLDialect::PostgreSQL => lists.lists[p.list].0,
+                _ => p.list,
+            };
+            let lists::InPredicate {
+                start, end, not_in, ..
+            } = p;
+            let (start, end) = (shifted(*start), shifted(*end));
+            quote!(sqlx_type::ListPredicate {
+                list: #list,
+                start: #start,
+                end: #end,
+                not_in: #not_in,
+            })
+        })
+        .collect();
+    // The query is expanded by a chain of conversions of the runtime query
+    let mut convert = match order_at {
+        Some(_) => quote!(ordered),
+        None => quote!(#query),
+    };
+    if !fragments.is_empty() {
+        let ranges = fragments.iter().map(|f| {
+            let (start, end) = (shifted(f.start), shifted(f.end));
+            quote!((#start, #end))
+        });
+        convert = quote!(sqlx_type::convert_fragments(&#convert, &[#(#ranges),*], &active));
+    }
+    if !predicates.is_empty() {
+        convert = quote!(sqlx_type::convert_empty_lists(&#convert, &sizes, &[#(#predicates),*]));
+    }
+    let mut key = Vec::new();
+    let mut sizes = quote!();
+    if let SQLDialect::PostgreSQL = dialect {
+        // Arguments after lists, fragments and _ORDER_ are renumbered
+        if !list_lengths.is_empty() || !fragments.is_empty() || order.is_some() {
+            sizes = quote!(let sizes = [#(#arg_lengths),*];);
+            key.extend((0..arg_lengths.len()).map(|i| quote!(sizes[#i])));
+            convert = quote!(sqlx_type::convert_dollar_tuple_list_query(
+                &#convert,
+                &sizes,
+                &[#(#arg_widths),*]
+            ));
+        }
+    } else if !list_lengths.is_empty() || !fragments.is_empty() {
+        sizes = quote!(let sizes = [#(#list_lengths),*];);
+        key.extend((0..list_lengths.len()).map(|i| quote!(sizes[#i])));
+        key.extend((0..fragments.len()).map(|k| quote!(active[#k] as usize)));
+        convert = quote!(sqlx_type::convert_tuple_list_query(
+            &#convert,
+            &sizes,
+            &[#(#list_widths),*]
+        ));
+    }
+    let mut ordered = quote!();
+    if let (Some(order), Some(at)) = (order, order_at) {
+        let name = &arg_names[order];
+        let quote = match dialect {
+            SQLDialect::MariaDB => '`',
+            SQLDialect::Sqlite | SQLDialect::PostgreSQL => '"',
+        };
+        key.push(quote!(#name.key()));
+        ordered = quote!(let ordered = sqlx_type::convert_order(
+            #query,
+            #at,
+            #name.column_name(),
+            #name.direction,
+            #quote
+        ););
+    }
+
+    // The expanded queries are cached per call site, since list sizes are bucketed only a
+    // few distinct queries are constructed. Queries with too many arguments fail without being
+    // expanded, so lists too long to be bucketed are not cached
+    let query = if key.is_empty() {
         quote!(#query)
     } else {
-        quote!(
-            &sqlx_type::convert_list_query(#query, &[#(#list_lengths),*])
-        )
+        quote!({
+            static CACHE: sqlx_type::QueryCache = sqlx_type::QueryCache::new();
+            #sizes
+            let key = [#(#key),*];
+            CACHE.get_within(&key, args_count, #max_arguments, || {
+                #ordered
+                #convert
+            }).unwrap_or(#query)
+        })
+    };
+
+    // A fragment is active when all of its arguments are given
+    let active = fragments.iter().map(|f| {
+        let names = f.args.iter().filter_map(|a| arg_names.get(*a));
+        quote!(true #(&& #names.is_some())*)
+    });
+    let active = if fragments.is_empty() {
+        quote!()
+    } else {
+        quote!(let active = [#(#active),*];)
     };
 
     (
@@ -297,12 +615,23 @@ fn quote_args(
             let mut size_hints = 0;
             let mut args_count = 0;
             #(#arg_bindings)*
+            #active
+            #(#fragment_counts)*
 
             let mut query_args = <#cls as ::sqlx::database::Database>::Arguments::default();
             query_args.reserve(args_count, size_hints);
             let mut e = Ok(());
             #(#arg_add)*
-            let query_args = e.and_then(|()| Ok(query_args));
+            let query_args = e.and_then(|()| {
+                if args_count > #max_arguments {
+                    return Err(::std::format!(
+                        "Too many arguments: {} > {}",
+                        args_count,
+                        #max_arguments
+                    ).into());
+                }
+                Ok(query_args)
+            });
         },
         query,
     )
@@ -327,43 +656,49 @@ fn issues_to_errors(issues: Vec<Issue>, source: &str, span: Span) -> Vec<proc_ma
     Vec::new()
 }
 
+/// Type the query with each combination of optional fragments, returning the errors of the
+/// first combination that fails
+///
+/// The query with all fragments is typed by the caller.
+fn check_fragments(
+    query: &str,
+    fragments: &[fragments::Fragment],
+    span: Span,
+    schemas: &Schemas,
+    dialect: &SQLDialect,
+    options: &TypeOptions,
+) -> Vec<proc_macro2::TokenStream> {
+    let all = (1 << fragments.len()) - 1;
+    for enabled in (0..all).rev() {
+        let combination = fragments::combination(query, fragments, enabled);
+        let typed = lists::ListQuery::new(&combination, dialect).typed;
+        let mut issues = sql_type::Issues::new(&typed);
+        type_statement(schemas, &typed, &mut issues, options);
+        let errors = issues_to_errors(issues.into_vec(), &typed, span);
+        if !errors.is_empty() {
+            let disabled: Vec<_> = (0..fragments.len())
+                .filter(|i| enabled & (1 << i) == 0)
+                .map(|i| (i + 1).to_string())
+                .collect();
+            let message = format!(
+                "Query is invalid without optional fragment {}",
+                disabled.join(", ")
+            );
+            let mut out = vec![syn::Error::new(span, message).to_compile_error()];
+            out.extend(errors);
+            return out;
+        }
+    }
+    Vec::new()
+}
+
 fn construct_row(
     columns: &[SelectTypeColumn],
 ) -> (Vec<proc_macro2::TokenStream>, Vec<proc_macro2::TokenStream>) {
     let mut row_members = Vec::new();
     let mut row_construct = Vec::new();
     for (i, c) in columns.iter().enumerate() {
-        let mut t = match c.type_.t {
-            sql_type::Type::U8 => quote! {u8},
-            sql_type::Type::I8 => quote! {i8},
-            sql_type::Type::U16 => quote! {u16},
-            sql_type::Type::I16 => quote! {i16},
-            sql_type::Type::U32 => quote! {u32},
-            sql_type::Type::I32 => quote! {i32},
-            sql_type::Type::U64 => quote! {u64},
-            sql_type::Type::I64 => quote! {i64},
-            sql_type::Type::Base(sql_type::BaseType::Any) => todo!("from_any"),
-            sql_type::Type::Base(sql_type::BaseType::Bool) => quote! {bool},
-            sql_type::Type::Base(sql_type::BaseType::Bytes) => quote! {Vec<u8>},
-            sql_type::Type::Base(sql_type::BaseType::Date) => quote! {chrono::NaiveDate},
-            sql_type::Type::Base(sql_type::BaseType::DateTime) => quote! {chrono::NaiveDateTime},
-            sql_type::Type::Base(sql_type::BaseType::Float) => quote! {f64},
-            sql_type::Type::Base(sql_type::BaseType::Integer) => quote! {i64},
-            sql_type::Type::Base(sql_type::BaseType::String) => quote! {String},
-            sql_type::Type::Base(sql_type::BaseType::Time) => todo!("from_time"),
-            sql_type::Type::Base(sql_type::BaseType::TimeInterval) => todo!("from_time_interval"),
-            sql_type::Type::Base(sql_type::BaseType::TimeStamp) => {
-                quote! {sqlx::types::chrono::DateTime<sqlx::types::chrono::Utc>}
-            }
-            sql_type::Type::Null => todo!("from_null"),
-            sql_type::Type::Invalid => quote! {i64},
-            sql_type::Type::Enum(_) => quote! {String},
-            sql_type::Type::Set(_) => quote! {String},
-            sql_type::Type::Args(_, _) => todo!("from_args"),
-            sql_type::Type::F32 => quote! {f32},
-            sql_type::Type::F64 => quote! {f64},
-            sql_type::Type::JSON => quote! {String},
-        };
+        let t = rust_type(&c.type_, Usage::Row);
         let name = match &c.name {
             Some(v) => v,
             None => continue,
@@ -378,9 +713,6 @@ fn construct_row(
             continue;
         };
 
-        if !c.type_.not_null {
-            t = quote! {Option<#t>};
-        }
         row_members.push(quote! {
             #ident : #t
         });
@@ -392,74 +724,224 @@ fn construct_row(
 }
 
 struct Query {
+    attrs: Vec<syn::Attribute>,
+    struct_: Option<(syn::Visibility, Ident)>,
+    enum_: Option<(syn::Visibility, Ident)>,
     query: String,
     query_span: Span,
     args: Vec<Expr>,
+    fragments: Vec<fragments::Fragment>,
     last_span: Span,
 }
 
+/// Arguments given by name as `name = expr`
+type NamedArgs = Vec<(Ident, Expr)>;
+
+/// Parse the arguments following the query, where `name = expr` gives a named argument
+fn parse_args(
+    input: syn::parse::ParseStream,
+    last_span: &mut Span,
+) -> syn::Result<(Vec<Expr>, NamedArgs)> {
+    let mut args = Vec::new();
+    let mut named = Vec::new();
+    while !input.is_empty() {
+        let _ = input.parse::<syn::token::Comma>()?;
+        if input.is_empty() {
+            break;
+        }
+        let arg = input.parse::<Expr>()?;
+        *last_span = arg.span();
+        match arg {
+            Expr::Assign(syn::ExprAssign { left, right, .. }) => {
+                let Expr::Path(syn::ExprPath { path, .. }) = *left else {
+                    return Err(syn::Error::new(left.span(), "Expected argument name"));
+                };
+                let Some(name) = path.get_ident() else {
+                    return Err(syn::Error::new(path.span(), "Expected argument name"));
+                };
+                named.push((name.clone(), *right));
+            }
+            arg => args.push(arg),
+        }
+    }
+    Ok((args, named))
+}
+
+/// Resolve the named arguments and find the optional fragments of a query
+fn parse_fragments(
+    query: &str,
+    query_span: Span,
+    args: Vec<Expr>,
+    named: NamedArgs,
+) -> syn::Result<fragments::FragmentQuery> {
+    let (_, dialect) = SCHEMAS.deref();
+    let names = fragments::argument_names(query);
+    if let Some((name, _)) = named.iter().find(|(n, _)| !names.iter().any(|m| n == m)) {
+        return Err(syn::Error::new(name.span(), "unused named argument"));
+    }
+    fragments::FragmentQuery::new(query, args, &named, query_span, dialect)
+}
+
 impl Parse for Query {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(syn::Attribute::parse_outer)?;
+        let vis = input.parse::<syn::Visibility>()?;
+        let (mut struct_, mut enum_) = (None, None);
+        if input.peek(Token![struct]) {
+            let _ = input.parse::<Token![struct]>()?;
+            let name = input.parse::<Ident>()?;
+            let _ = input.parse::<syn::token::Comma>()?;
+            struct_ = Some((vis, name));
+        } else if input.peek(Token![enum]) {
+            let _ = input.parse::<Token![enum]>()?;
+            let name = input.parse::<Ident>()?;
+            let _ = input.parse::<syn::token::Comma>()?;
+            enum_ = Some((vis, name));
+        } else if !matches!(vis, syn::Visibility::Inherited) {
+            return Err(syn::Error::new(vis.span(), "Expected struct or enum"));
+        }
         let query_ = Punctuated::<LitStr, Token![+]>::parse_separated_nonempty(input)?;
         let query: String = query_.iter().map(LitStr::value).collect();
         let query_span = query_.span();
         let mut last_span = query_span;
-        let mut args = Vec::new();
-        while !input.is_empty() {
-            let _ = input.parse::<syn::token::Comma>()?;
-            if input.is_empty() {
-                break;
-            }
-            let arg = input.parse::<Expr>()?;
-            last_span = arg.span();
-            args.push(arg);
+        let (args, named) = parse_args(input, &mut last_span)?;
+        if (struct_.is_some() || enum_.is_some()) && (!args.is_empty() || !named.is_empty()) {
+            return Err(syn::Error::new(
+                last_span,
+                "Arguments are not given when defining a struct or enum",
+            ));
         }
+        let fragments::FragmentQuery {
+            query,
+            args,
+            fragments,
+        } = parse_fragments(&query, query_span, args, named)?;
         Ok(Self {
+            attrs,
+            struct_,
+            enum_,
             query,
             query_span,
             args,
+            fragments,
             last_span,
         })
     }
 }
 
-/// Statically checked SQL query, similarly to sqlx::query!.
+/// Attributes to put on a generated row struct
 ///
-/// This expands to an instance of query::Map that outputs an ad-hoc anonymous struct type.
-#[proc_macro]
-pub fn query(input: TokenStream) -> TokenStream {
-    let query = syn::parse_macro_input!(input as Query);
-    let (schemas, dialect) = SCHEMAS.deref();
-    let options = TypeOptions::new()
-        .dialect(dialect.clone())
-        .arguments(match &dialect {
-            SQLDialect::MariaDB => SQLArguments::QuestionMark,
-            SQLDialect::Sqlite => SQLArguments::QuestionMark,
-            SQLDialect::PostgreSQL => SQLArguments::Dollar,
-        })
-        .list_hack(true);
-    let mut issues = sql_type::Issues::new(&query.query);
-    let stmt = type_statement(schemas, &query.query, &mut issues, &options);
+/// When the serde feature is enabled and the attributes derive serde traits, the derives given
+/// by a `serde::` path are pointed at the serde re-exported by sqlx-type, as is the code they
+/// generate unless a `#[serde(crate = ...)]` is given, so that the using crate does not need
+/// a direct dependency on serde.
+#[cfg(feature = "serde")]
+fn row_attributes(attrs: &[syn::Attribute]) -> proc_macro2::TokenStream {
+    let mut derives_serde = false;
+    let mut names_crate = false;
+    let mut out = Vec::new();
+    for attr in attrs {
+        if let (true, syn::Meta::List(list)) = (attr.path().is_ident("serde"), &attr.meta) {
+            names_crate |= list
+                .tokens
+                .clone()
+                .into_iter()
+                .any(|t| matches!(t, proc_macro2::TokenTree::Ident(i) if i == "crate"));
+        } else if attr.path().is_ident("derive") {
+            if let Ok(paths) =
+                attr.parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+            {
+                let paths = paths.iter().map(|p| {
+                    let last = &p.segments.last().unwrap().ident;
+                    if last != "Serialize" && last != "Deserialize" {
+                        return quote!(#p);
+                    }
+                    derives_serde = true;
+                    match p.segments.first() {
+                        Some(first) if p.segments.len() == 2 && first.ident == "serde" => {
+                            quote_spanned!(p.span()=> sqlx_type::serde::#last)
+                        }
+                        _ => quote!(#p),
+                    }
+                });
+                let paths: Vec<_> = paths.collect();
+                out.push(quote!(#[derive(#(#paths),*)]));
+                continue;
+            }
+        }
+        out.push(quote!(#attr));
+    }
+    if derives_serde && !names_crate {
+        out.push(quote!(#[serde(crate = "sqlx_type::serde")]));
+    }
+    quote! {#(#out)*}
+}
+
+#[cfg(not(feature = "serde"))]
+fn row_attributes(attrs: &[syn::Attribute]) -> proc_macro2::TokenStream {
+    quote! {#(#attrs)*}
+}
+
+/// Define a named struct with a member for each column returned by the query
+fn quote_struct(
+    errors: &[proc_macro2::TokenStream],
+    query: &Query,
+    vis: &syn::Visibility,
+    name: &Ident,
+    columns: Option<&[SelectTypeColumn]>,
+) -> proc_macro2::TokenStream {
     let sp = SCHEMA_PATH.as_path().to_str().unwrap();
+    let Some(columns) = columns else {
+        return syn::Error::new(
+            query.query_span,
+            "Only queries returning rows can be used to define a struct",
+        )
+        .to_compile_error();
+    };
+    let (row_members, _) = construct_row(columns);
+    let attrs = row_attributes(&query.attrs);
+    quote! {
+        #(#errors)*
+        const _: &[u8] = std::include_bytes!(#sp);
 
-    let mut errors = issues_to_errors(issues.into_vec(), &query.query, query.query_span);
-    match &stmt {
-        sql_type::StatementType::Select { columns, arguments } => {
-            let (args_tokens, q) = quote_args(
-                &mut errors,
-                &query.query,
-                query.last_span,
-                &query.args,
-                arguments,
-                dialect,
-            );
+        #attrs
+        #vis struct #name {
+            #(#vis #row_members),*
+        }
+    }
+}
+
+fn quote_query(
+    errors: &mut Vec<proc_macro2::TokenStream>,
+    query: &Query,
+    arguments: &[(sql_type::ArgumentKey<'_>, sql_type::FullType)],
+    columns: Option<&[SelectTypeColumn]>,
+    dialect: &SQLDialect,
+) -> proc_macro2::TokenStream {
+    let sp = SCHEMA_PATH.as_path().to_str().unwrap();
+    let (args_tokens, q) = quote_args(
+        errors,
+        QueryArgs {
+            query: &query.query,
+            args: &query.args,
+            fragments: &query.fragments,
+            last_span: query.last_span,
+        },
+        arguments,
+        columns,
+        dialect,
+    );
+    match columns {
+        Some(columns) => {
             let (row_members, row_construct) = construct_row(columns);
-            let s = quote! { {
+            let attrs = row_attributes(&query.attrs);
+            quote! { {
                 use ::sqlx::Arguments as _;
                 let _ = std::include_bytes!(#sp);
                 #(#errors; )*
-                #args_tokens;
+                #args_tokens
 
+                #attrs
                 struct Row {
                     #(#row_members),*
                 };
@@ -468,219 +950,146 @@ pub fn query(input: TokenStream) -> TokenStream {
                         #(#row_construct),*
                     }
                 )
-            }};
-            s.into()
+            }}
+        }
+        None => quote! { {
+            use ::sqlx::Arguments as _;
+            let _ = std::include_bytes!(#sp);
+            #(#errors; )*
+            #args_tokens
+            sqlx::__query_with_result(#q, query_args)
+        }},
+    }
+}
+
+/// Statically checked SQL query, similarly to sqlx::query!.
+///
+/// This expands to an instance of query::Map that outputs an ad-hoc anonymous struct type.
+///
+/// Outer attributes given before the query string are put on the generated struct, this can
+/// be used to derive traits for the rows:
+/// ```ignore
+/// let row = query!(#[derive(Debug, Clone)] "SELECT `id` FROM `t1`").fetch_one(&pool).await?;
+/// ```
+///
+/// Parts of the query in square brackets are optional, and only included when all of their
+/// arguments are `Some`. Arguments written as `?name` are given as `name = expr`, or taken
+/// from the variable `name`:
+/// ```ignore
+/// let rows = query!("SELECT `id` FROM `t1` WHERE TRUE [AND `cu8` = ?status]", status = Some(3))
+///     .fetch_all(&pool).await?;
+/// ```
+///
+/// When used in item position with a struct name instead of arguments, a named struct with
+/// the columns of the query is defined instead. This can be used with [query_as!]:
+/// ```ignore
+/// query!(#[derive(Debug)] pub struct IdRow, "SELECT `id` FROM `t1` WHERE `id`=?");
+///
+/// let row: IdRow = query_as!(IdRow, "SELECT `id` FROM `t1` WHERE `id`=?", 42)
+///     .fetch_one(&pool).await?;
+/// ```
+///
+/// Similarly `query!(pub enum Name, "...")` defines an enum with a variant for each column of
+/// the query, used to choose the column of an `ORDER BY _ORDER_` at runtime:
+/// ```ignore
+/// query!(pub enum IdOrder, "SELECT `id` FROM `t1`");
+///
+/// let rows = query!("SELECT `id` FROM `t1` ORDER BY _ORDER_", Order::desc(IdOrder::Id))
+///     .fetch_all(&pool).await?;
+/// ```
+#[proc_macro]
+pub fn query(input: TokenStream) -> TokenStream {
+    let input = match splice::splice(input.into(), quote!(sqlx_type::query), false) {
+        Ok(input) => input.into(),
+        Err(call) => return call.into(),
+    };
+    let query = syn::parse_macro_input!(input as Query);
+    expand_query(&query).into()
+}
+
+fn expand_query(query: &Query) -> proc_macro2::TokenStream {
+    let (schemas, dialect) = SCHEMAS.deref();
+    let options = type_options(dialect);
+    let typed = lists::ListQuery::new(&query.query, dialect).typed;
+    let mut issues = sql_type::Issues::new(&typed);
+    let stmt = type_statement(schemas, &typed, &mut issues, &options);
+
+    let mut errors = issues_to_errors(issues.into_vec(), &typed, query.query_span);
+    if errors.is_empty() {
+        errors = check_fragments(
+            &query.query,
+            &query.fragments,
+            query.query_span,
+            schemas,
+            dialect,
+            &options,
+        );
+    }
+    if let Some((vis, name)) = &query.struct_ {
+        let columns = match &stmt {
+            sql_type::StatementType::Select { columns, .. } => Some(columns.as_slice()),
+            sql_type::StatementType::Delete { returning, .. }
+            | sql_type::StatementType::Insert { returning, .. }
+            | sql_type::StatementType::Update { returning, .. }
+            | sql_type::StatementType::Replace { returning, .. } => returning.as_deref(),
+            sql_type::StatementType::Invalid => {
+                return quote! {#(#errors)*};
+            }
+        };
+        return quote_struct(&errors, query, vis, name, columns);
+    }
+    if let Some((vis, name)) = &query.enum_ {
+        let columns = match &stmt {
+            sql_type::StatementType::Select { columns, .. } => columns.as_slice(),
+            sql_type::StatementType::Invalid => return quote! {#(#errors)*},
+            _ => {
+                return syn::Error::new(
+                    query.query_span,
+                    "Only SELECT queries can be used to define an enum",
+                )
+                .to_compile_error()
+            }
+        };
+        let sp = SCHEMA_PATH.as_path().to_str().unwrap();
+        let order = paging::quote_order_enum(&query.attrs, vis, name, columns, query.query_span);
+        return quote! {
+            #(#errors)*
+            const _: &[u8] = std::include_bytes!(#sp);
+            #order
+        };
+    }
+    match &stmt {
+        sql_type::StatementType::Select { columns, arguments } => {
+            quote_query(&mut errors, query, arguments, Some(columns), dialect)
         }
         sql_type::StatementType::Delete {
             arguments,
             returning,
-        } => {
-            let (args_tokens, q) = quote_args(
-                &mut errors,
-                &query.query,
-                query.last_span,
-                &query.args,
-                arguments,
-                dialect,
-            );
-            let s = match returning.as_ref() {
-                Some(returning) => {
-                    let (row_members, row_construct) = construct_row(returning);
-                    quote! { {
-                        use ::sqlx::Arguments as _;
-                        let _ = std::include_bytes!(#sp);
-                        #(#errors; )*
-                        #args_tokens
-
-                        struct Row {
-                            #(#row_members),*
-                        };
-                        sqlx::__query_with_result(#q, query_args).map(|row|
-                            Row{
-                                #(#row_construct),*
-                            }
-                        )
-                    }}
-                }
-                None => quote! { {
-                    use ::sqlx::Arguments as _;
-                    #(#errors; )*
-                    #args_tokens
-                    sqlx::__query_with_result(#q, query_args)
-                }
-                },
-            };
-            s.into()
         }
-        sql_type::StatementType::Insert {
+        | sql_type::StatementType::Insert {
             arguments,
             returning,
             ..
-        } => {
-            let (args_tokens, q) = quote_args(
-                &mut errors,
-                &query.query,
-                query.last_span,
-                &query.args,
-                arguments,
-                dialect,
-            );
-            let s = match returning.as_ref() {
-                Some(returning) => {
-                    let (row_members, row_construct) = construct_row(returning);
-                    quote! { {
-                        use ::sqlx::Arguments as _;
-                        let _ = std::include_bytes!(#sp);
-                        #(#errors; )*
-                        #args_tokens
-
-                        struct Row {
-                            #(#row_members),*
-                        };
-                        sqlx::__query_with_result(#q, query_args).map(|row|
-                            Row{
-                                #(#row_construct),*
-                            }
-                        )
-                    }}
-                }
-                None => quote! { {
-                    use ::sqlx::Arguments as _;
-                    #(#errors; )*
-                    #args_tokens
-                    sqlx::__query_with_result(#q, query_args)
-                }
-                },
-            };
-            s.into()
         }
-        sql_type::StatementType::Update {
+        | sql_type::StatementType::Update {
             arguments,
             returning,
-        } => {
-            let (args_tokens, q) = quote_args(
-                &mut errors,
-                &query.query,
-                query.last_span,
-                &query.args,
-                arguments,
-                dialect,
-            );
-
-            let s = match returning.as_ref() {
-                Some(returning) => {
-                    let (row_members, row_construct) = construct_row(returning);
-                    quote! { {
-                        use ::sqlx::Arguments as _;
-                        let _ = std::include_bytes!(#sp);
-                        #(#errors; )*
-                        #args_tokens
-
-                        struct Row {
-                            #(#row_members),*
-                        };
-                        sqlx::__query_with_result(#q, query_args).map(|row|
-                            Row{
-                                #(#row_construct),*
-                            }
-                        )
-                    }}
-                }
-                None => quote! { {
-                    use ::sqlx::Arguments as _;
-                    #(#errors; )*
-                    #args_tokens
-                    sqlx::__query_with_result(#q, query_args)
-                }
-                },
-            };
-            s.into()
         }
-        sql_type::StatementType::Replace {
+        | sql_type::StatementType::Replace {
             arguments,
             returning,
-        } => {
-            let (args_tokens, q) = quote_args(
-                &mut errors,
-                &query.query,
-                query.last_span,
-                &query.args,
-                arguments,
-                dialect,
-            );
-            let s = match returning.as_ref() {
-                Some(returning) => {
-                    let (row_members, row_construct) = construct_row(returning);
-                    quote! { {
-                        use ::sqlx::Arguments as _;
-                        let _ = std::include_bytes!(#sp);
-                        #(#errors; )*
-                        #args_tokens
-
-                        struct Row {
-                            #(#row_members),*
-                        };
-                        sqlx::__query_with_result(#q, query_args).map(|row|
-                            Row{
-                                #(#row_construct),*
-                            }
-                        )
-                    }}
-                }
-                None => quote! { {
-                    use ::sqlx::Arguments as _;
-                    #(#errors; )*
-                    #args_tokens
-                    sqlx::__query_with_result(#q, query_args)
-                }
-                },
-            };
-            s.into()
-        }
-        sql_type::StatementType::Invalid => {
-            let s = quote! { {
-                #(#errors; )*;
-                todo!("Invalid")
-            }};
-            s.into()
-        }
+        } => quote_query(&mut errors, query, arguments, returning.as_deref(), dialect),
+        sql_type::StatementType::Invalid => quote! { {
+            #(#errors; )*;
+            todo!("Invalid")
+        }},
     }
 }
 
 fn construct_row2(columns: &[SelectTypeColumn]) -> Vec<proc_macro2::TokenStream> {
     let mut row_construct = Vec::new();
     for (i, c) in columns.iter().enumerate() {
-        let mut t = match c.type_.t {
-            sql_type::Type::U8 => quote! {u8},
-            sql_type::Type::I8 => quote! {i8},
-            sql_type::Type::U16 => quote! {u16},
-            sql_type::Type::I16 => quote! {i16},
-            sql_type::Type::U32 => quote! {u32},
-            sql_type::Type::I32 => quote! {i32},
-            sql_type::Type::U64 => quote! {u64},
-            sql_type::Type::I64 => quote! {i64},
-            sql_type::Type::Base(sql_type::BaseType::Any) => todo!("from_any"),
-            sql_type::Type::Base(sql_type::BaseType::Bool) => quote! {bool},
-            sql_type::Type::Base(sql_type::BaseType::Bytes) => quote! {Vec<u8>},
-            sql_type::Type::Base(sql_type::BaseType::Date) => quote! {chrono::NaiveDate},
-            sql_type::Type::Base(sql_type::BaseType::DateTime) => quote! {chrono::NaiveDateTime},
-            sql_type::Type::Base(sql_type::BaseType::Float) => quote! {f64},
-            sql_type::Type::Base(sql_type::BaseType::Integer) => quote! {i64},
-            sql_type::Type::Base(sql_type::BaseType::String) => quote! {String},
-            sql_type::Type::Base(sql_type::BaseType::Time) => todo!("from_time"),
-            sql_type::Type::Base(sql_type::BaseType::TimeInterval) => todo!("from_time_interval"),
-            sql_type::Type::Base(sql_type::BaseType::TimeStamp) => {
-                quote! {sqlx::types::chrono::DateTime<sqlx::types::chrono::Utc>}
-            }
-            sql_type::Type::Null => todo!("from_null"),
-            sql_type::Type::Invalid => quote! {i64},
-            sql_type::Type::Enum(_) => quote! {String},
-            sql_type::Type::Set(_) => quote! {String},
-            sql_type::Type::Args(_, _) => todo!("from_args"),
-            sql_type::Type::F32 => quote! {f32},
-            sql_type::Type::F64 => quote! {f64},
-            sql_type::Type::JSON => quote! {String},
-        };
+        let t = rust_type(&c.type_, Usage::Member);
         let name = match &c.name {
             Some(v) => v,
             None => continue,
@@ -695,9 +1104,6 @@ fn construct_row2(columns: &[SelectTypeColumn]) -> Vec<proc_macro2::TokenStream>
             continue;
         };
 
-        if !c.type_.not_null {
-            t = quote! {Option<#t>};
-        }
         row_construct.push(quote! {
             #ident: sqlx_type::arg_out::<#t, _, #i>(sqlx::Row::get(&row, #i))
         });
@@ -706,16 +1112,17 @@ fn construct_row2(columns: &[SelectTypeColumn]) -> Vec<proc_macro2::TokenStream>
 }
 
 struct QueryAs {
-    as_: Ident,
+    as_: syn::Path,
     query: String,
     query_span: Span,
     args: Vec<Expr>,
+    fragments: Vec<fragments::Fragment>,
     last_span: Span,
 }
 
 impl Parse for QueryAs {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let as_ = input.parse::<Ident>()?;
+        let as_ = input.parse::<syn::Path>()?;
         let _ = input.parse::<syn::token::Comma>()?;
 
         let query_ = Punctuated::<LitStr, Token![+]>::parse_separated_nonempty(input)?;
@@ -723,81 +1130,49 @@ impl Parse for QueryAs {
         let query_span = query_.span();
 
         let mut last_span = query_span;
-        let mut args = Vec::new();
-        while !input.is_empty() {
-            let _ = input.parse::<syn::token::Comma>()?;
-            if input.is_empty() {
-                break;
-            }
-            let arg = input.parse::<Expr>()?;
-            last_span = arg.span();
-            args.push(arg);
-        }
+        let (args, named) = parse_args(input, &mut last_span)?;
+        let fragments::FragmentQuery {
+            query,
+            args,
+            fragments,
+        } = parse_fragments(&query, query_span, args, named)?;
         Ok(Self {
             as_,
             query,
             query_span,
             args,
+            fragments,
             last_span,
         })
     }
 }
 
-/// A variant of query! which takes a path to an explicitly defined struct as the output type.
-///
-/// This lets you return the struct from a function or add your own trait implementations.
-#[proc_macro]
-pub fn query_as(input: TokenStream) -> TokenStream {
-    let query_as = syn::parse_macro_input!(input as QueryAs);
-    let (schemas, dialect) = SCHEMAS.deref();
-    let options = TypeOptions::new()
-        .dialect(dialect.clone())
-        .arguments(match &dialect {
-            SQLDialect::MariaDB => SQLArguments::QuestionMark,
-            SQLDialect::Sqlite => SQLArguments::QuestionMark,
-            SQLDialect::PostgreSQL => SQLArguments::Dollar,
-        })
-        .list_hack(true);
-    let mut issues = sql_type::Issues::new(&query_as.query);
-    let stmt = type_statement(schemas, &query_as.query, &mut issues, &options);
-
-    let mut errors = issues_to_errors(issues.into_vec(), &query_as.query, query_as.query_span);
-    match &stmt {
-        sql_type::StatementType::Select { columns, arguments } => {
-            let (args_tokens, q) = quote_args(
-                &mut errors,
-                &query_as.query,
-                query_as.last_span,
-                &query_as.args,
-                arguments,
-                dialect,
-            );
-
-            let row_construct = construct_row2(columns);
-            let row = query_as.as_;
-            let s = quote! { {
-                use ::sqlx::Arguments as _;
-                #(#errors; )*
-                #args_tokens
-                sqlx::__query_with_result(#q, query_args).map(|row|
-                    #row{
-                        #(#row_construct),*
-                    }
-                )
-            }};
-            //println!("TOKENS: {}", s);
-            s.into()
+fn quote_query_as(
+    errors: &mut Vec<proc_macro2::TokenStream>,
+    query_as: &QueryAs,
+    stmt: &sql_type::StatementType,
+    dialect: &SQLDialect,
+) -> proc_macro2::TokenStream {
+    let (arguments, columns) = match stmt {
+        sql_type::StatementType::Select { columns, arguments } => (arguments, columns),
+        sql_type::StatementType::Insert {
+            arguments,
+            returning: Some(returning),
+            ..
         }
+        | sql_type::StatementType::Replace {
+            arguments,
+            returning: Some(returning),
+        } => (arguments, returning),
         sql_type::StatementType::Delete { .. } => {
             errors.push(
                 syn::Error::new(query_as.query_span, "DELETE not support in query_as")
                     .to_compile_error(),
             );
-            quote! { {
+            return quote! { {
                 #(#errors; )*
                 todo!("delete")
-            }}
-            .into()
+            }};
         }
         sql_type::StatementType::Insert {
             returning: None, ..
@@ -809,50 +1184,20 @@ pub fn query_as(input: TokenStream) -> TokenStream {
                 )
                 .to_compile_error(),
             );
-            quote! { {
+            return quote! { {
                 #(#errors; )*
                 todo!("insert")
-            }}
-            .into()
-        }
-        sql_type::StatementType::Insert {
-            arguments,
-            returning: Some(returning),
-            ..
-        } => {
-            let (args_tokens, q) = quote_args(
-                &mut errors,
-                &query_as.query,
-                query_as.last_span,
-                &query_as.args,
-                arguments,
-                dialect,
-            );
-
-            let row_construct = construct_row2(returning);
-            let row = query_as.as_;
-            let s = quote! { {
-                use ::sqlx::Arguments as _;
-                #(#errors; )*
-                #args_tokens
-                sqlx::__query_with_result(#q, query_args).map(|row|
-                    #row{
-                        #(#row_construct),*
-                    }
-                )
             }};
-            s.into()
         }
         sql_type::StatementType::Update { .. } => {
             errors.push(
                 syn::Error::new(query_as.query_span, "UPDATE not support in query_as")
                     .to_compile_error(),
             );
-            quote! { {
+            return quote! { {
                 #(#errors; )*
                 todo!("update")
-            }}
-            .into()
+            }};
         }
         sql_type::StatementType::Replace {
             returning: None, ..
@@ -864,44 +1209,994 @@ pub fn query_as(input: TokenStream) -> TokenStream {
                 )
                 .to_compile_error(),
             );
-            quote! { {
+            return quote! { {
                 #(#errors; )*
                 todo!("replace")
-            }}
-            .into()
+            }};
         }
-        sql_type::StatementType::Replace {
-            arguments,
-            returning: Some(returning),
-            ..
-        } => {
+        sql_type::StatementType::Invalid => {
+            return quote! { {
+                #(#errors; )*;
+                todo!("invalid")
+            }};
+        }
+    };
+
+    let (args_tokens, q) = quote_args(
+        errors,
+        QueryArgs {
+            query: &query_as.query,
+            args: &query_as.args,
+            fragments: &query_as.fragments,
+            last_span: query_as.last_span,
+        },
+        arguments,
+        Some(columns),
+        dialect,
+    );
+
+    let row_construct = construct_row2(columns);
+    let row = &query_as.as_;
+    quote! { {
+        use ::sqlx::Arguments as _;
+        #(#errors; )*
+        #args_tokens
+        sqlx::__query_with_result(#q, query_args).map(|row|
+            #row{
+                #(#row_construct),*
+            }
+        )
+    }}
+}
+
+/// A variant of query! which takes a path to an explicitly defined struct as the output type.
+///
+/// This lets you return the struct from a function or add your own trait implementations.
+#[proc_macro]
+pub fn query_as(input: TokenStream) -> TokenStream {
+    let input = match splice::splice(input.into(), quote!(sqlx_type::query_as), false) {
+        Ok(input) => input.into(),
+        Err(call) => return call.into(),
+    };
+    let query_as = syn::parse_macro_input!(input as QueryAs);
+    expand_query_as(&query_as).into()
+}
+
+fn expand_query_as(query_as: &QueryAs) -> proc_macro2::TokenStream {
+    let (schemas, dialect) = SCHEMAS.deref();
+    let options = type_options(dialect);
+    let typed = lists::ListQuery::new(&query_as.query, dialect).typed;
+    let mut issues = sql_type::Issues::new(&typed);
+    let stmt = type_statement(schemas, &typed, &mut issues, &options);
+
+    let mut errors = issues_to_errors(issues.into_vec(), &typed, query_as.query_span);
+    if errors.is_empty() {
+        errors = check_fragments(
+            &query_as.query,
+            &query_as.fragments,
+            query_as.query_span,
+            schemas,
+            dialect,
+            &options,
+        );
+    }
+    quote_query_as(&mut errors, query_as, &stmt, dialect)
+}
+
+/// Wrap a query in the fetch wrapper matching the number of rows it returns
+fn wrap_fetch(query: &str, tokens: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let (_, dialect) = SCHEMAS.deref();
+    let wrapper = match cardinality::cardinality(query, dialect) {
+        cardinality::Cardinality::None => quote!(sqlx_type::FetchNone),
+        cardinality::Cardinality::One => quote!(sqlx_type::FetchOne),
+        cardinality::Cardinality::Optional => quote!(sqlx_type::FetchOptional),
+        cardinality::Cardinality::Many => quote!(sqlx_type::FetchAll),
+    };
+    quote!(#wrapper(#tokens))
+}
+
+/// A variant of query! where the number of rows returned is inferred from the statement.
+///
+/// The query is wrapped in a type with a `fetch` method returning `T` for statements returning
+/// exactly one row, such as aggregates without GROUP BY, `Option<T>` for statements returning
+/// at most one row, such as a lookup on a primary or unique key or `LIMIT 1`, and `Vec<T>`
+/// otherwise. For statements without rows, `fetch` executes the statement.
+#[proc_macro]
+pub fn query_fetch(input: TokenStream) -> TokenStream {
+    let input = match splice::splice(input.into(), quote!(sqlx_type::query_fetch), false) {
+        Ok(input) => input.into(),
+        Err(call) => return call.into(),
+    };
+    let query = syn::parse_macro_input!(input as Query);
+    if let Some((vis, _)) = query.struct_.as_ref().or(query.enum_.as_ref()) {
+        return syn::Error::new(vis.span(), "Items can not be defined with query_fetch")
+            .to_compile_error()
+            .into();
+    }
+    wrap_fetch(&query.query, expand_query(&query)).into()
+}
+
+/// A variant of query_as! where the number of rows returned is inferred from the statement.
+///
+/// See [query_fetch!] for a description of the returned type.
+#[proc_macro]
+pub fn query_as_fetch(input: TokenStream) -> TokenStream {
+    let input = match splice::splice(input.into(), quote!(sqlx_type::query_as_fetch), false) {
+        Ok(input) => input.into(),
+        Err(call) => return call.into(),
+    };
+    let query_as = syn::parse_macro_input!(input as QueryAs);
+    wrap_fetch(&query_as.query, expand_query_as(&query_as)).into()
+}
+
+/// How the rows of a query defined by queries! are fetched
+enum Fetch {
+    /// Execute the query, returning the query result
+    Execute,
+    /// Fetch exactly one row
+    One(syn::Path),
+    /// Fetch zero or one row
+    Optional(syn::Path),
+    /// Fetch all rows
+    All(syn::Path),
+}
+
+struct QueryFn {
+    attrs: Vec<syn::Attribute>,
+    vis: syn::Visibility,
+    name: Ident,
+    params: Vec<syn::PatType>,
+    ret: Option<syn::Type>,
+    fetch: Fetch,
+    query: String,
+    query_span: Span,
+    args: Vec<Expr>,
+    fragments: Vec<fragments::Fragment>,
+    last_span: Span,
+    /// The name of the cursor struct given as `#[keyset(Cursor)]`
+    keyset: Option<Ident>,
+}
+
+/// Find the struct path `T` and fetch mode in a return type of `T`, `Option<T>` or `Vec<T>`
+fn parse_fetch(ret: &syn::Type) -> syn::Result<Fetch> {
+    let syn::Type::Path(p) = ret else {
+        return Err(syn::Error::new(
+            ret.span(),
+            "Expected return type T, Option<T> or Vec<T> where T is a struct",
+        ));
+    };
+    let Some(last) = p.path.segments.last() else {
+        return Err(syn::Error::new(ret.span(), "Expected return type"));
+    };
+    if p.qself.is_none() && (last.ident == "Option" || last.ident == "Vec") {
+        if let syn::PathArguments::AngleBracketed(a) = &last.arguments {
+            if let (1, Some(syn::GenericArgument::Type(inner))) = (a.args.len(), a.args.first()) {
+                let syn::Type::Path(inner) = inner else {
+                    return Err(syn::Error::new(inner.span(), "Expected a struct"));
+                };
+                return Ok(if last.ident == "Option" {
+                    Fetch::Optional(inner.path.clone())
+                } else {
+                    Fetch::All(inner.path.clone())
+                });
+            }
+        }
+    }
+    Ok(Fetch::One(p.path.clone()))
+}
+
+/// Parse the parameters of a function, returning them and the span of the parentheses
+fn parse_params(input: syn::parse::ParseStream) -> syn::Result<(Vec<syn::PatType>, Span)> {
+    let content;
+    let paren = syn::parenthesized!(content in input);
+    let mut params = Vec::new();
+    for arg in Punctuated::<syn::FnArg, Token![,]>::parse_terminated(&content)? {
+        match arg {
+            syn::FnArg::Typed(t) if matches!(*t.pat, syn::Pat::Ident(_)) => params.push(t),
+            arg => return Err(syn::Error::new(arg.span(), "Expected name: type")),
+        }
+    }
+    Ok((params, paren.span.join()))
+}
+
+/// Parse an optional `-> T` giving the fetch mode of a query
+fn parse_return(input: syn::parse::ParseStream) -> syn::Result<(Option<syn::Type>, Fetch)> {
+    if !input.peek(Token![->]) {
+        return Ok((None, Fetch::Execute));
+    }
+    let _ = input.parse::<Token![->]>()?;
+    let ret = input.parse::<syn::Type>()?;
+    let fetch = parse_fetch(&ret)?;
+    Ok((Some(ret), fetch))
+}
+
+impl Parse for QueryFn {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut attrs = input.call(syn::Attribute::parse_outer)?;
+        let mut keyset = None;
+        if let Some(i) = attrs.iter().position(|a| a.path().is_ident("keyset")) {
+            keyset = Some(attrs.remove(i).parse_args::<Ident>()?);
+        }
+        let vis = input.parse::<syn::Visibility>()?;
+        let _ = input.parse::<Token![fn]>()?;
+        let name = input.parse::<Ident>()?;
+        let (params, mut last_span) = parse_params(input)?;
+        let (ret, fetch) = parse_return(input)?;
+        let _ = input.parse::<Token![=]>()?;
+        let query_ = Punctuated::<LitStr, Token![+]>::parse_separated_nonempty(input)?;
+        let query: String = query_.iter().map(LitStr::value).collect();
+        let query_span = query_.span();
+        if params.is_empty() {
+            last_span = query_span;
+        }
+        let _ = input.parse::<Token![;]>()?;
+
+        // Parameters used as named arguments are bound by name, the rest in order
+        let names = fragments::argument_names(&query);
+        let mut args = Vec::new();
+        let mut named = Vec::new();
+        for p in &params {
+            let syn::Pat::Ident(pat) = &*p.pat else {
+                continue;
+            };
+            let ident = &pat.ident;
+            if names.iter().any(|n| ident == n) {
+                named.push((ident.clone(), syn::parse_quote!(#ident)));
+            } else {
+                args.push(syn::parse_quote!(#ident));
+            }
+        }
+        let fragments::FragmentQuery {
+            query,
+            args,
+            fragments,
+        } = parse_fragments(&query, query_span, args, named)?;
+        Ok(Self {
+            attrs,
+            vis,
+            name,
+            params,
+            ret,
+            fetch,
+            query,
+            query_span,
+            args,
+            fragments,
+            last_span,
+            keyset,
+        })
+    }
+}
+
+struct Queries(Vec<QueryFn>);
+
+impl Parse for Queries {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut fns = Vec::new();
+        while !input.is_empty() {
+            fns.push(input.parse()?);
+        }
+        Ok(Self(fns))
+    }
+}
+
+/// Type the query of a function, returning the statement and the errors found
+fn type_query_fn<'a>(
+    f: &QueryFn,
+    typed: &'a str,
+    schemas: &'a Schemas<'a>,
+    dialect: &SQLDialect,
+    options: &TypeOptions,
+) -> (sql_type::StatementType<'a>, Vec<proc_macro2::TokenStream>) {
+    let mut issues = sql_type::Issues::new(typed);
+    let stmt = type_statement(schemas, typed, &mut issues, options);
+    let mut errors = issues_to_errors(issues.into_vec(), typed, f.query_span);
+    if errors.is_empty() {
+        errors = check_fragments(
+            &f.query,
+            &f.fragments,
+            f.query_span,
+            schemas,
+            dialect,
+            options,
+        );
+    }
+    (stmt, errors)
+}
+
+/// The expression running the query of a function, the method fetching its result and the
+/// type of the result
+fn quote_fn_body(
+    mut errors: Vec<proc_macro2::TokenStream>,
+    f: &QueryFn,
+    stmt: &sql_type::StatementType,
+    dialect: &SQLDialect,
+) -> (
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+) {
+    let cls = database(dialect);
+    let (body, fetch) = match &f.fetch {
+        Fetch::Execute => {
+            let (arguments, columns) = match &stmt {
+                sql_type::StatementType::Select { arguments, columns } => {
+                    (arguments.as_slice(), Some(columns.as_slice()))
+                }
+                sql_type::StatementType::Delete {
+                    arguments,
+                    returning,
+                }
+                | sql_type::StatementType::Insert {
+                    arguments,
+                    returning,
+                    ..
+                }
+                | sql_type::StatementType::Update {
+                    arguments,
+                    returning,
+                }
+                | sql_type::StatementType::Replace {
+                    arguments,
+                    returning,
+                } => (arguments.as_slice(), returning.as_deref()),
+                sql_type::StatementType::Invalid => (&[][..], None),
+            };
             let (args_tokens, q) = quote_args(
                 &mut errors,
-                &query_as.query,
-                query_as.last_span,
-                &query_as.args,
+                QueryArgs {
+                    query: &f.query,
+                    args: &f.args,
+                    fragments: &f.fragments,
+                    last_span: f.last_span,
+                },
                 arguments,
+                columns,
                 dialect,
             );
+            (
+                quote! { {
+                    use ::sqlx::Arguments as _;
+                    #(#errors; )*
+                    #args_tokens
+                    sqlx::__query_with_result(#q, query_args)
+                }},
+                quote!(execute),
+            )
+        }
+        Fetch::One(row) | Fetch::Optional(row) | Fetch::All(row) => {
+            let query_as = QueryAs {
+                as_: row.clone(),
+                query: f.query.clone(),
+                query_span: f.query_span,
+                args: f.args.clone(),
+                fragments: f.fragments.clone(),
+                last_span: f.last_span,
+            };
+            let fetch = match &f.fetch {
+                Fetch::One(_) => quote!(fetch_one),
+                Fetch::Optional(_) => quote!(fetch_optional),
+                _ => quote!(fetch_all),
+            };
+            (quote_query_as(&mut errors, &query_as, stmt, dialect), fetch)
+        }
+    };
+    let ret = match &f.ret {
+        Some(ret) => quote!(#ret),
+        None => quote!(<#cls as sqlx::Database>::QueryResult),
+    };
+    (body, fetch, ret)
+}
 
-            let row_construct = construct_row2(returning);
-            let row = query_as.as_;
-            let s = quote! { {
-                use ::sqlx::Arguments as _;
-                #(#errors; )*
-                #args_tokens
-                sqlx::__query_with_result(#q, query_args).map(|row|
-                    #row{
-                        #(#row_construct),*
+fn quote_query_fn(
+    f: &QueryFn,
+    schemas: &Schemas,
+    dialect: &SQLDialect,
+    options: &TypeOptions,
+) -> proc_macro2::TokenStream {
+    let typed = lists::ListQuery::new(&f.query, dialect).typed;
+    let (stmt, errors) = type_query_fn(f, &typed, schemas, dialect, options);
+    let (body, fetch, ret) = quote_fn_body(errors, f, &stmt, dialect);
+    let cls = database(dialect);
+    let keyset = f
+        .keyset
+        .as_ref()
+        .map(|cursor| quote_keyset(f, cursor, &stmt, schemas, dialect, options));
+    let QueryFn {
+        attrs,
+        vis,
+        name,
+        params,
+        ..
+    } = f;
+    quote! {
+        #(#attrs)*
+        #vis async fn #name<'c, E>(executor: E, #(#params),*) -> Result<#ret, sqlx::Error>
+        where
+            E: sqlx::Executor<'c, Database = #cls>,
+        {
+            #body.#fetch(executor).await
+        }
+
+        #keyset
+    }
+}
+
+/// Define the cursor struct and the function fetching the page after a cursor, for a query
+/// function with `#[keyset(Cursor)]`
+fn quote_keyset(
+    f: &QueryFn,
+    cursor: &Ident,
+    stmt: &sql_type::StatementType,
+    schemas: &Schemas,
+    dialect: &SQLDialect,
+    options: &TypeOptions,
+) -> proc_macro2::TokenStream {
+    let Fetch::All(row) = &f.fetch else {
+        return syn::Error::new(
+            f.name.span(),
+            "Keyset pagination requires a return type of Vec<T>",
+        )
+        .to_compile_error();
+    };
+    let sql_type::StatementType::Select { columns, .. } = stmt else {
+        // The errors are reported by the query function
+        return quote! {};
+    };
+    let next = match keyset::next_page(
+        &f.query,
+        &f.args,
+        &f.fragments,
+        &format_ident!("cursor"),
+        f.query_span,
+        dialect,
+    ) {
+        Ok(next) => next,
+        Err(e) => return e.to_compile_error(),
+    };
+    let mut keys = Vec::new();
+    for key in &next.keys {
+        let Some(c) = columns
+            .iter()
+            .find(|c| c.name.as_ref().is_some_and(|n| n.value == key))
+        else {
+            return syn::Error::new(
+                f.query_span,
+                format!("The ORDER BY column {key} is not returned by the query"),
+            )
+            .to_compile_error();
+        };
+        if !c.type_.not_null {
+            return syn::Error::new(
+                f.query_span,
+                format!("The ORDER BY column {key} may be NULL"),
+            )
+            .to_compile_error();
+        }
+        keys.push(c.clone());
+    }
+    let (members, _) = construct_row(&keys);
+    let names: Vec<Ident> = next
+        .keys
+        .iter()
+        .map(|k| Ident::new_raw(k, f.name.span()))
+        .collect();
+
+    let mut attrs = vec![syn::parse_quote!(#[doc = " The page after `cursor`"])];
+    attrs.extend(
+        f.attrs
+            .iter()
+            .filter(|a| !a.path().is_ident("doc"))
+            .cloned(),
+    );
+    let mut params = vec![syn::parse_quote!(cursor: &#cursor)];
+    params.extend(f.params.iter().cloned());
+    let after = QueryFn {
+        attrs,
+        vis: f.vis.clone(),
+        name: format_ident!("{}_after", f.name),
+        params,
+        ret: f.ret.clone(),
+        fetch: Fetch::All(row.clone()),
+        query: next.query,
+        query_span: f.query_span,
+        args: next.args,
+        fragments: next.fragments,
+        last_span: f.last_span,
+        keyset: None,
+    };
+    let after = quote_query_fn(&after, schemas, dialect, options);
+    let vis = &f.vis;
+    let doc = format!(" The sort key of a row returned by `{}`", f.name);
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, Debug)]
+        #vis struct #cursor {
+            #(#vis #members),*
+        }
+
+        impl #cursor {
+            /// The cursor of the last row of a page, or None when the page is empty
+            #vis fn last(rows: &[#row]) -> Option<Self> {
+                rows.last().map(|row| Self {
+                    #(#names: Clone::clone(&row.#names)),*
+                })
+            }
+        }
+
+        #after
+    }
+}
+
+/// Define typed async functions running queries
+///
+/// Each function takes an executor followed by the declared arguments, which are bound to the
+/// arguments of the query in order. The declared return type determines how rows are fetched:
+/// `Option<T>` uses fetch_optional, `Vec<T>` uses fetch_all and any other `T` uses fetch_one,
+/// where `T` is a struct with a member for each returned column as for [query_as!]. Without a
+/// return type the query is executed and the query result is returned.
+///
+/// With `#[keyset(Cursor)]` on a function returning `Vec<T>`, a struct `Cursor` with a member
+/// for each column of the ORDER BY is defined, along with a function `name_after` taking a
+/// `&Cursor` before the declared arguments and returning the rows after it. The columns of
+/// the ORDER BY must be returned by the query, not null and all ascending or all descending.
+///
+/// ```ignore
+/// queries! {
+///     /// Lookup a row by id
+///     pub fn get_row(id: i32) -> Option<Row> = "SELECT `id`, `ctext` FROM `t1` WHERE `id`=?";
+///     pub fn delete_row(id: i32) = "DELETE FROM `t1` WHERE `id`=?";
+/// }
+/// ```
+#[proc_macro]
+pub fn queries(input: TokenStream) -> TokenStream {
+    let input = match splice::splice(input.into(), quote!(sqlx_type::queries), false) {
+        Ok(input) => input.into(),
+        Err(call) => return call.into(),
+    };
+    let queries = syn::parse_macro_input!(input as Queries);
+    let (schemas, dialect) = SCHEMAS.deref();
+    let options = type_options(dialect);
+    let sp = SCHEMA_PATH.as_path().to_str().unwrap();
+    let fns = queries
+        .0
+        .iter()
+        .map(|f| quote_query_fn(f, schemas, dialect, &options));
+    quote! {
+        const _: &[u8] = std::include_bytes!(#sp);
+        #(#fns)*
+    }
+    .into()
+}
+
+/// A function of query_batch! running several statements in a transaction
+struct QueryBatch {
+    attrs: Vec<syn::Attribute>,
+    vis: syn::Visibility,
+    name: Ident,
+    params: Vec<syn::PatType>,
+    /// The statements, each typed and run as the query of a function of queries!
+    statements: Vec<QueryFn>,
+}
+
+impl Parse for QueryBatch {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(syn::Attribute::parse_outer)?;
+        let vis = input.parse::<syn::Visibility>()?;
+        let _ = input.parse::<Token![fn]>()?;
+        let name = input.parse::<Ident>()?;
+        let (params, _) = parse_params(input)?;
+        let content;
+        let brace = syn::braced!(content in input);
+        let mut statements = Vec::new();
+        while !content.is_empty() {
+            let query_ = Punctuated::<LitStr, Token![+]>::parse_separated_nonempty(&content)?;
+            let query: String = query_.iter().map(LitStr::value).collect();
+            let query_span = query_.span();
+            let mut last_span = query_span;
+            // The arguments end at `->` or `;`, which expressions may not stop at
+            let args = content.step(|cursor| {
+                let mut rest = *cursor;
+                let mut tokens = proc_macro2::TokenStream::new();
+                while let Some((t, next)) = rest.token_tree() {
+                    if let Some((p, after)) = rest.punct() {
+                        let arrow = p.as_char() == '-'
+                            && p.spacing() == proc_macro2::Spacing::Joint
+                            && after.punct().is_some_and(|(q, _)| q.as_char() == '>');
+                        if arrow || p.as_char() == ';' {
+                            break;
+                        }
                     }
+                    tokens.extend([t]);
+                    rest = next;
+                }
+                Ok((tokens, rest))
+            })?;
+            let (args, named) =
+                (|input: syn::parse::ParseStream| parse_args(input, &mut last_span))
+                    .parse2(args)?;
+            let (ret, fetch) = parse_return(&content)?;
+            let _ = content.parse::<Token![;]>()?;
+            let fragments::FragmentQuery {
+                query,
+                args,
+                fragments,
+            } = parse_fragments(&query, query_span, args, named)?;
+            statements.push(QueryFn {
+                attrs: Vec::new(),
+                vis: vis.clone(),
+                name: name.clone(),
+                params: params.clone(),
+                ret,
+                fetch,
+                query,
+                query_span,
+                args,
+                fragments,
+                last_span,
+                keyset: None,
+            });
+        }
+        if statements.is_empty() {
+            return Err(syn::Error::new(brace.span.join(), "Expected statements"));
+        }
+        Ok(Self {
+            attrs,
+            vis,
+            name,
+            params,
+            statements,
+        })
+    }
+}
+
+struct QueryBatches(Vec<QueryBatch>);
+
+impl Parse for QueryBatches {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut fns = Vec::new();
+        while !input.is_empty() {
+            fns.push(input.parse()?);
+        }
+        Ok(Self(fns))
+    }
+}
+
+fn quote_query_batch(
+    b: &QueryBatch,
+    schemas: &Schemas,
+    dialect: &SQLDialect,
+    options: &TypeOptions,
+) -> proc_macro2::TokenStream {
+    let cls = database(dialect);
+    // The names of the generated bindings are not visible to the statements, so they do not
+    // shadow parameters or variables of the same name
+    let acquire = Ident::new("acquire", Span::mixed_site());
+    let tx = Ident::new("tx", Span::mixed_site());
+    let mut steps = Vec::new();
+    let mut rets = Vec::new();
+    let mut results = Vec::new();
+    for (i, s) in b.statements.iter().enumerate() {
+        let typed = lists::ListQuery::new(&s.query, dialect).typed;
+        let (stmt, errors) = type_query_fn(s, &typed, schemas, dialect, options);
+        let (body, fetch, ret) = quote_fn_body(errors, s, &stmt, dialect);
+        let result = format_ident!("r{}", i, span = Span::mixed_site());
+        steps.push(quote! {
+            let #result = #body.#fetch(&mut *#tx).await?;
+        });
+        rets.push(ret);
+        results.push(result);
+    }
+    let QueryBatch {
+        attrs,
+        vis,
+        name,
+        params,
+        ..
+    } = b;
+    quote! {
+        #(#attrs)*
+        #vis async fn #name<'c, A>(#acquire: A, #(#params),*) -> Result<(#(#rets,)*), sqlx::Error>
+        where
+            A: sqlx::Acquire<'c, Database = #cls>,
+        {
+            let mut #tx = #acquire.begin().await?;
+            #(#steps)*
+            #tx.commit().await?;
+            Ok((#(#results,)*))
+        }
+    }
+}
+
+/// Define typed async functions running several statements in a transaction
+///
+/// The body of each function is a list of statements, each written as the query and arguments
+/// of [query!] followed by an optional return type as for [queries!], and ended by `;`. The
+/// statements are typed against the schema in order. The function takes anything that can
+/// begin a transaction, such as a pool or a connection, followed by the declared arguments,
+/// runs the statements in a transaction and returns a tuple of their results.
+///
+/// ```ignore
+/// query_batch! {
+///     /// Update the text of a row and return the row
+///     pub fn set_text(id: i32, text: &str) {
+///         "UPDATE `t1` SET `ctext` = ?text WHERE `id` = ?id";
+///         "SELECT `id`, `ctext` FROM `t1` WHERE `id` = ?id" -> Option<Row>;
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn query_batch(input: TokenStream) -> TokenStream {
+    let input = match splice::splice(input.into(), quote!(sqlx_type::query_batch), true) {
+        Ok(input) => input.into(),
+        Err(call) => return call.into(),
+    };
+    let batches = syn::parse_macro_input!(input as QueryBatches);
+    let (schemas, dialect) = SCHEMAS.deref();
+    let options = type_options(dialect);
+    let sp = SCHEMA_PATH.as_path().to_str().unwrap();
+    let fns = batches
+        .0
+        .iter()
+        .map(|b| quote_query_batch(b, schemas, dialect, &options));
+    quote! {
+        const _: &[u8] = std::include_bytes!(#sp);
+        #(#fns)*
+    }
+    .into()
+}
+
+struct SqlFragment {
+    attrs: Vec<syn::Attribute>,
+    name: Ident,
+    sql: LitStr,
+}
+
+impl Parse for SqlFragment {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(syn::Attribute::parse_outer)?;
+        let name = input.parse::<Ident>()?;
+        let _ = input.parse::<Token![=]>()?;
+        let sql_ = Punctuated::<LitStr, Token![+]>::parse_separated_nonempty(input)?;
+        let sql: String = sql_.iter().map(LitStr::value).collect();
+        Ok(Self {
+            attrs,
+            name,
+            sql: LitStr::new(&sql, sql_.span()),
+        })
+    }
+}
+
+/// Define a named fragment of SQL, spliced into the queries of the other macros as `{name}`
+///
+/// The fragment is defined as a macro `name`, so it is in scope like a macro defined with
+/// macro_rules, and can be used in other modules after `pub(crate) use name;`. The query
+/// including the fragment is typed as a whole, and the arguments of the fragment are given
+/// with the arguments of the query. Named arguments such as `?tenant` are convenient for
+/// this, as they are bound by name wherever the fragment is used:
+/// ```ignore
+/// sql_fragment!(small = "`cu8` < 10 AND `ctext` <> ?text");
+///
+/// let rows = query!("SELECT `id` FROM `t1` WHERE {small} AND `id` > ?", 42, text = "x")
+///     .fetch_all(&pool).await?;
+/// ```
+#[proc_macro]
+pub fn sql_fragment(input: TokenStream) -> TokenStream {
+    let fragment = syn::parse_macro_input!(input as SqlFragment);
+    splice::quote_sql_fragment(&fragment.attrs, &fragment.name, &fragment.sql).into()
+}
+
+struct SqlEnum {
+    attrs: Vec<syn::Attribute>,
+    vis: syn::Visibility,
+    name: Ident,
+}
+
+impl Parse for SqlEnum {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(syn::Attribute::parse_outer)?;
+        let vis = input.parse::<syn::Visibility>()?;
+        let _ = input.parse::<Token![enum]>()?;
+        let name = input.parse::<Ident>()?;
+        Ok(Self { attrs, vis, name })
+    }
+}
+
+/// Define a Rust enum for a PostgreSQL enum type of the schema
+///
+/// The enum type is the one whose name in camel case is the name of the enum, and the enum
+/// has a variant for each of its values, also in camel case. Columns of the type are returned
+/// as the enum, which must be in scope where they are queried, and the enum is accepted as an
+/// argument for them:
+/// ```ignore
+/// // CREATE TYPE mood AS ENUM ('sad', 'ok', 'happy');
+/// sql_enum!(pub enum Mood);
+///
+/// let rows = query!("SELECT id FROM people WHERE mood = $1", Mood::Happy)
+///     .fetch_all(&pool).await?;
+/// ```
+#[proc_macro]
+pub fn sql_enum(input: TokenStream) -> TokenStream {
+    let e = syn::parse_macro_input!(input as SqlEnum);
+    enums::quote_sql_enum(&e.attrs, &e.vis, &e.name).into()
+}
+
+struct InsertMany {
+    query: String,
+    query_span: Span,
+    rows: Expr,
+}
+
+impl Parse for InsertMany {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let query_ = Punctuated::<LitStr, Token![+]>::parse_separated_nonempty(input)?;
+        let query: String = query_.iter().map(LitStr::value).collect();
+        let query_span = query_.span();
+        let _ = input.parse::<syn::token::Comma>()?;
+        let rows = input.parse::<Expr>()?;
+        if input.peek(syn::token::Comma) {
+            let _ = input.parse::<syn::token::Comma>()?;
+        }
+        Ok(Self {
+            query,
+            query_span,
+            rows,
+        })
+    }
+}
+
+/// Find the number of columns inserted by a statement containing _ROWS_
+fn rows_width(query: &str, dialect: &SQLDialect) -> usize {
+    let probe = query.replacen("_ROWS_", "(NULL)", 1);
+    let options = sql_parse::ParseOptions::new().dialect(dialect.clone());
+    let mut issues = sql_parse::Issues::new(&probe);
+    match sql_parse::parse_statement(&probe, &mut issues, &options) {
+        Some(sql_parse::Statement::InsertReplace(i)) => i.columns.len(),
+        _ => 0,
+    }
+}
+
+/// Statically checked insertion of many rows
+///
+/// The statement must contain `VALUES _ROWS_` and the explicit list of columns to insert.
+/// The second argument is an iterator of rows, where each row is a tuple with a member for
+/// each column, or a single value if only one column is inserted. Structs can be inserted
+/// by mapping them to tuples.
+///
+/// This expands to an [InsertMany] whose execute method inserts the rows using as few
+/// statements as the limit on the number of arguments in a statement allows, in a
+/// transaction. Fragments of [sql_fragment!] can be used in the query if they take no
+/// arguments, as only the rows are given as arguments.
+///
+/// ```ignore
+/// insert_many!(
+///     "INSERT INTO `t1` (`cu8`, `ctext`) VALUES _ROWS_",
+///     rows.iter().map(|r| (r.cu8, &r.ctext))
+/// ).execute(&pool).await?;
+/// ```
+#[proc_macro]
+pub fn insert_many(input: TokenStream) -> TokenStream {
+    let input = match splice::splice(input.into(), quote!(sqlx_type::insert_many), false) {
+        Ok(input) => input.into(),
+        Err(call) => return call.into(),
+    };
+    let insert = syn::parse_macro_input!(input as InsertMany);
+    let (schemas, dialect) = SCHEMAS.deref();
+    let sp = SCHEMA_PATH.as_path().to_str().unwrap();
+
+    if insert.query.matches("_ROWS_").count() != 1 {
+        return syn::Error::new(insert.query_span, "Expected exactly one _ROWS_ in query")
+            .to_compile_error()
+            .into();
+    }
+    let width = rows_width(&insert.query, dialect);
+    if width == 0 {
+        return syn::Error::new(
+            insert.query_span,
+            "Expected INSERT or REPLACE with an explicit list of columns",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // Type the statement inserting a single row
+    let cls = database(dialect);
+    let (placeholders, row) = match dialect {
+        SQLDialect::MariaDB | SQLDialect::Sqlite => (
+            quote!(sqlx_type::Placeholders::QuestionMark),
+            vec!["?".to_string(); width],
+        ),
+        SQLDialect::PostgreSQL => (
+            quote!(sqlx_type::Placeholders::Dollar),
+            (1..=width).map(|i| format!("${i}")).collect(),
+        ),
+    };
+    let max_arguments = max_arguments(dialect);
+    let template = insert
+        .query
+        .replacen("_ROWS_", &format!("({})", row.join(", ")), 1);
+    let options = type_options(dialect).list_hack(false);
+    let mut issues = sql_type::Issues::new(&template);
+    let stmt = type_statement(schemas, &template, &mut issues, &options);
+    let mut errors = issues_to_errors(issues.into_vec(), &template, insert.query_span);
+
+    let arguments = match &stmt {
+        sql_type::StatementType::Insert {
+            arguments,
+            returning: None,
+            ..
+        }
+        | sql_type::StatementType::Replace {
+            arguments,
+            returning: None,
+        } => arguments.as_slice(),
+        sql_type::StatementType::Invalid => &[],
+        _ => {
+            errors.push(
+                syn::Error::new(
+                    insert.query_span,
+                    "Expected INSERT or REPLACE without RETURNING",
                 )
-            }};
-            s.into()
+                .to_compile_error(),
+            );
+            &[]
+        }
+    };
+    let inv = sql_type::FullType::invalid();
+    let mut types = vec![&inv; width];
+    for (k, v) in arguments {
+        match k {
+            sql_type::ArgumentKey::Index(i) if *i < width => types[*i] = v,
+            _ => {
+                errors.push(
+                    syn::Error::new(
+                        insert.query_span,
+                        "Arguments outside of _ROWS_ are not supported",
+                    )
+                    .to_compile_error(),
+                );
+                break;
+            }
         }
-        sql_type::StatementType::Invalid => quote! { {
-            #(#errors; )*;
-            todo!("invalid")
-        }}
-        .into(),
     }
+
+    let names = (0..width)
+        .map(|i| format_ident!("arg{}", i))
+        .collect::<Vec<_>>();
+    let types: Vec<_> = types
+        .into_iter()
+        .map(|t| rust_type(t, Usage::Arg))
+        .collect();
+    let bound = names.iter().map(|name| quote_bind(quote!(#name)));
+    let pattern = if width == 1 {
+        quote!(#(#names)*)
+    } else {
+        quote!((#(#names),*))
+    };
+    let query = &insert.query;
+    let rows = &insert.rows;
+    quote! { {
+        use ::sqlx::Arguments as _;
+        let _ = std::include_bytes!(#sp);
+        #(#errors; )*
+        static CACHE: sqlx_type::QueryCache = sqlx_type::QueryCache::new();
+        sqlx_type::InsertMany::<#cls, _, _>::new(
+            &CACHE,
+            #query,
+            #width,
+            #max_arguments,
+            #placeholders,
+            ::std::iter::IntoIterator::into_iter(#rows),
+            |query_args, row| {
+                let #pattern = row;
+                if false {
+                    #(sqlx_type::check_arg::<#types, _>(&#names);)*
+                    ::std::panic!();
+                }
+                #(query_args.add(#bound)?;)*
+                Ok(())
+            },
+        )
+    }}
+    .into()
 }
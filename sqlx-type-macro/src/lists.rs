@@ -0,0 +1,741 @@
+//! Rewriting of queries using the _LIST_ hack
+//!
+//! A `_LIST_` is a single argument expanding to a placeholder for each element at runtime.
+//! The typer handles `_LIST_` directly for `?` arguments, but numbers them independently of
+//! `$n` arguments, and does not support row values as in `(a, b) IN (_LIST_)` or
+//! `(a, b) > (?, ?)`, so such queries are rewritten before typing.
+use sql_parse::{Expression, Spanned};
+use sql_type::SQLDialect;
+
+use crate::ast;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Question,
+    Dollar(usize),
+    List,
+    Order,
+    Word,
+    Other,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Token {
+    pub kind: Kind,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// The length of the dollar quoted string `$tag$...$tag$` at the start of rest
+fn dollar_quoted(rest: &str) -> Option<usize> {
+    let open = rest[1..].find('$')? + 2;
+    let tag = &rest[..open];
+    if !tag[1..open - 1]
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    Some(
+        rest[open..]
+            .find(tag)
+            .map_or(rest.len(), |i| open + i + tag.len()),
+    )
+}
+
+/// Split query into the tokens relevant for finding arguments and lists
+///
+/// The expansion of `$n` arguments at runtime, in `convert_dollar_tuple_list_query` of
+/// sqlx-type, skips the same string literals, quoted identifiers and comments, and the two
+/// must be kept in sync.
+pub(crate) fn tokenize(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while let Some(c) = query[pos..].chars().next() {
+        let rest = &query[pos..];
+        let (kind, len) = match c {
+            '\'' | '"' | '`' => (Kind::Other, rest[1..].find(c).map_or(rest.len(), |i| i + 2)),
+            '-' if rest.starts_with("--") => (Kind::Other, rest.find('\n').unwrap_or(rest.len())),
+            '/' if rest.starts_with("/*") => {
+                (Kind::Other, rest.find("*/").map_or(rest.len(), |i| i + 2))
+            }
+            '(' => (Kind::Open, 1),
+            ')' => (Kind::Close, 1),
+            '[' => (Kind::OpenBracket, 1),
+            ']' => (Kind::CloseBracket, 1),
+            ',' => (Kind::Comma, 1),
+            '?' => (Kind::Question, 1),
+            '$' => {
+                let digits = rest[1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len() - 1);
+                match rest[1..digits + 1].parse() {
+                    Ok(n) => (Kind::Dollar(n), digits + 1),
+                    Err(_) => (Kind::Other, dollar_quoted(rest).unwrap_or(digits + 1)),
+                }
+            }
+            // An escape string, where a quote is escaped by a backslash
+            'E' | 'e' if rest[1..].starts_with('\'') => {
+                let mut len = rest.len();
+                let mut chars = rest.char_indices().skip(2);
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '\'' => {
+                            len = i + 1;
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+                (Kind::Other, len)
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let len = rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                match &rest[..len] {
+                    "_LIST_" => (Kind::List, len),
+                    "_ORDER_" => (Kind::Order, len),
+                    _ => (Kind::Word, len),
+                }
+            }
+            c if c.is_whitespace() => {
+                pos += c.len_utf8();
+                continue;
+            }
+            c => (Kind::Other, c.len_utf8()),
+        };
+        tokens.push(Token {
+            kind,
+            start: pos,
+            end: pos + len,
+        });
+        pos += len;
+    }
+    tokens
+}
+
+/// A `(e1, e2, ...) IN (_LIST_)` predicate
+struct Tuple {
+    /// Index of the token opening the row value
+    open: usize,
+    /// Index of the list token
+    list: usize,
+    /// Ranges of the elements of the row value in the query
+    elements: Vec<(usize, usize)>,
+    /// True for NOT IN
+    not_in: bool,
+}
+
+/// Find the row value on the left hand side of IN, if the list token at `i` is the only
+/// element on the right hand side
+fn tuple(query: &str, tokens: &[Token], i: usize) -> Option<Tuple> {
+    let kind = |j: usize| tokens.get(j).map(|t| t.kind);
+    let word = |j: usize, w: &str| {
+        kind(j) == Some(Kind::Word) && query[tokens[j].start..tokens[j].end].eq_ignore_ascii_case(w)
+    };
+    if i < 4 || kind(i - 1) != Some(Kind::Open) || kind(i + 1) != Some(Kind::Close) {
+        return None;
+    }
+    if !word(i - 2, "IN") {
+        return None;
+    }
+    let not_in = word(i - 3, "NOT");
+    let close = if not_in { i - 4 } else { i - 3 };
+    if kind(close) != Some(Kind::Close) {
+        return None;
+    }
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut open = close;
+    loop {
+        open = open.checked_sub(1)?;
+        match tokens[open].kind {
+            Kind::Close => depth += 1,
+            Kind::Open if depth == 0 => break,
+            Kind::Open => depth -= 1,
+            Kind::Comma if depth == 0 => commas.push(tokens[open].start),
+            _ => (),
+        }
+    }
+    if commas.is_empty() {
+        return None;
+    }
+    commas.reverse();
+    let mut elements = Vec::new();
+    let mut start = tokens[open].end;
+    for end in commas
+        .into_iter()
+        .chain(std::iter::once(tokens[close].start))
+    {
+        elements.push((start, end));
+        start = end + 1;
+    }
+    Some(Tuple {
+        open,
+        list: i,
+        elements,
+        not_in,
+    })
+}
+
+/// A `(e1, e2, ...) op (x1, x2, ...)` comparison of row values
+struct Comparison {
+    /// Index of the token opening the left hand side
+    open: usize,
+    /// Index of the token closing the right hand side
+    close: usize,
+    /// The comparison operator
+    op: (usize, usize),
+    /// Ranges of the elements of the left hand side in the query
+    lhs: Vec<(usize, usize)>,
+    /// Ranges of the elements of the right hand side in the query
+    rhs: Vec<(usize, usize)>,
+}
+
+/// Find the closing token and the elements of a row value opened by the token at `open`
+fn row(tokens: &[Token], open: usize) -> Option<(usize, Vec<(usize, usize)>)> {
+    let mut depth = 0;
+    let mut elements = Vec::new();
+    let mut start = tokens.get(open)?.end;
+    for (i, t) in tokens.iter().enumerate().skip(open + 1) {
+        match t.kind {
+            Kind::Open => depth += 1,
+            Kind::Close if depth == 0 => {
+                elements.push((start, t.start));
+                return (elements.len() > 1).then_some((i, elements));
+            }
+            Kind::Close => depth -= 1,
+            Kind::Comma if depth == 0 => {
+                elements.push((start, t.start));
+                start = t.end;
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Find a comparison of row values starting at the token at `i`
+fn comparison(query: &str, tokens: &[Token], i: usize) -> Option<Comparison> {
+    if tokens[i].kind != Kind::Open {
+        return None;
+    }
+    // A parenthesis directly after a word is a function call, unless the word is a keyword
+    if let Some(prev) = i.checked_sub(1).map(|j| &tokens[j]) {
+        let word = &query[prev.start..prev.end];
+        if prev.kind == Kind::Word
+            && !["AND", "OR", "NOT", "WHERE", "ON", "HAVING", "WHEN"]
+                .iter()
+                .any(|k| word.eq_ignore_ascii_case(k))
+        {
+            return None;
+        }
+    }
+    let (close, lhs) = row(tokens, i)?;
+    let mut j = close + 1;
+    while tokens.get(j).is_some_and(|t| {
+        t.kind == Kind::Other
+            && matches!(&query[t.start..t.end], "<" | ">" | "=" | "!")
+            && (j == close + 1 || tokens[j - 1].end == t.start)
+    }) {
+        j += 1;
+    }
+    if j == close + 1 {
+        return None;
+    }
+    let op = (tokens[close + 1].start, tokens[j - 1].end);
+    if !matches!(
+        &query[op.0..op.1],
+        "=" | "<>" | "!=" | "<" | "<=" | ">" | ">="
+    ) {
+        return None;
+    }
+    if tokens.get(j)?.kind != Kind::Open {
+        return None;
+    }
+    let (close, rhs) = row(tokens, j)?;
+    (lhs.len() == rhs.len()).then_some(Comparison {
+        open: i,
+        close,
+        op,
+        lhs,
+        rhs,
+    })
+}
+
+/// Record that argument `index` of the typed query is `arg`
+fn set_typed_arg(typed_args: &mut Vec<(usize, usize)>, index: usize, arg: (usize, usize)) {
+    if typed_args.len() <= index {
+        typed_args.resize(index + 1, (usize::MAX, 0));
+    }
+    typed_args[index] = arg;
+}
+
+/// A query using the _LIST_ hack, rewritten for typing and for expansion at runtime
+pub(crate) struct ListQuery {
+    /// The query given to the typer
+    pub typed: String,
+    /// The query expanded at runtime, with the same offsets as the original query
+    pub runtime: String,
+    /// For each argument of the typed query the argument it belongs to, and the
+    /// element of the row value for tuple lists
+    typed_args: Vec<(usize, usize)>,
+    /// The argument and the number of elements in each row, for each list in order
+    pub lists: Vec<(usize, usize)>,
+    /// The argument and offset of each `_ORDER_` placeholder
+    pub orders: Vec<(usize, usize)>,
+    /// The arguments given as arrays to `ANY($n)` or `ALL($n)`
+    pub arrays: Vec<usize>,
+    /// The original query with the separators of row values replaced so it can be parsed
+    pub parsable: String,
+    /// With `$n` arguments the numbers taken by both a `$n` and a list or `_ORDER_`
+    pub collisions: Vec<usize>,
+}
+
+impl ListQuery {
+    pub(crate) fn new(query: &str, dialect: &SQLDialect) -> Self {
+        let dollar = matches!(dialect, SQLDialect::PostgreSQL);
+        let tokens = tokenize(query);
+
+        // Number the arguments and lists of the query. With `$n` arguments a list is
+        // numbered one higher than the highest argument before it
+        let mut args = vec![0; tokens.len()];
+        let mut next = 0;
+        for (i, t) in tokens.iter().enumerate() {
+            match t.kind {
+                Kind::Question if !dollar => {
+                    args[i] = next;
+                    next += 1;
+                }
+                Kind::Dollar(n) if dollar => next = next.max(n),
+                Kind::List | Kind::Order => {
+                    args[i] = next;
+                    next += 1;
+                }
+                _ => (),
+            }
+        }
+        let tuples: Vec<Tuple> = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.kind == Kind::List)
+            .filter_map(|(i, _)| tuple(query, &tokens, i))
+            .collect();
+        let mut comparisons = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            match comparison(query, &tokens, i) {
+                Some(c) => {
+                    i = c.close + 1;
+                    comparisons.push(c);
+                }
+                None => i += 1,
+            }
+        }
+        let lists = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.kind == Kind::List)
+            .map(|(i, _)| {
+                let width = tuples
+                    .iter()
+                    .find(|t| t.list == i)
+                    .map_or(1, |t| t.elements.len());
+                (args[i], width)
+            })
+            .collect();
+
+        // With `$n` arguments the lists are given by number at runtime, padded to keep the
+        // offsets of the original query
+        let mut runtime = String::with_capacity(query.len());
+        let mut pos = 0;
+        for (i, t) in tokens.iter().enumerate() {
+            if t.kind == Kind::List && dollar {
+                runtime.push_str(&query[pos..t.start]);
+                runtime.push_str(&format!("{:<6}", format!("${}", args[i] + 1)));
+                pos = t.end;
+            }
+        }
+        runtime.push_str(&query[pos..]);
+
+        // The separators of row values are replaced by `+` so the query can be parsed
+        let mut parsable = query.to_string();
+        let separators = tuples.iter().flat_map(|t| &t.elements[1..]).chain(
+            comparisons
+                .iter()
+                .flat_map(|c| c.lhs[1..].iter().chain(&c.rhs[1..])),
+        );
+        for (start, _) in separators {
+            parsable.replace_range(start - 1..*start, "+");
+        }
+
+        // Rewrite `(e1, e2) IN (_LIST_)` to `(e1 IN (_LIST_) AND e2 IN (_LIST_))` for the typer,
+        // with `$n` arguments the elements of the rows are given unused numbers. Similarly
+        // `(e1, e2) > (x1, x2)` is rewritten to `(e1 > x1 AND e2 > x2)`
+        let mut typed = String::with_capacity(query.len());
+        let mut typed_args = Vec::new();
+        let push_arg = |typed_args: &mut Vec<(usize, usize)>, j: usize| match tokens[j].kind {
+            Kind::Question if !dollar => typed_args.push((args[j], 0)),
+            Kind::Dollar(n) if dollar && n != 0 => set_typed_arg(typed_args, n - 1, (n - 1, 0)),
+            _ => (),
+        };
+        let mut fresh = next;
+        let mut arrays = Vec::new();
+        let mut pos = 0;
+        let mut i = 0;
+        while let Some(t) = tokens.get(i) {
+            if let Some(c) = comparisons.iter().find(|c| c.open == i) {
+                typed.push_str(&query[pos..t.start]);
+                typed.push('(');
+                for (k, (l, r)) in c.lhs.iter().zip(&c.rhs).enumerate() {
+                    if k != 0 {
+                        typed.push_str(" AND ");
+                    }
+                    for (side, (start, end)) in [l, r].into_iter().enumerate() {
+                        if side == 1 {
+                            typed.push(' ');
+                            typed.push_str(&query[c.op.0..c.op.1]);
+                            typed.push(' ');
+                        }
+                        typed.push_str(&query[*start..*end]);
+                        for (j, t) in tokens.iter().enumerate().take(c.close).skip(i) {
+                            if t.start >= *start && t.end <= *end {
+                                push_arg(&mut typed_args, j);
+                            }
+                        }
+                    }
+                }
+                typed.push(')');
+                i = c.close + 1;
+                pos = tokens[c.close].end;
+                continue;
+            }
+            match t.kind {
+                Kind::Question | Kind::Dollar(_) => push_arg(&mut typed_args, i),
+                Kind::List if dollar => {
+                    typed.push_str(&query[pos..t.start]);
+                    typed.push_str(&format!("{:<6}", format!("${}", args[i] + 1)));
+                    pos = t.end;
+                    set_typed_arg(&mut typed_args, args[i], (args[i], 0));
+                }
+                Kind::List => typed_args.push((args[i], 0)),
+                Kind::Order => {
+                    typed.push_str(&query[pos..t.start]);
+                    typed.push_str("1      ");
+                    pos = t.end;
+                }
+                // The typer does not know `x = ANY(array)`, so it is given `x = (array)` with
+                // the argument typed as an element of the array
+                Kind::Word
+                    if dollar
+                        && ["ANY", "ALL", "SOME"]
+                            .iter()
+                            .any(|k| query[t.start..t.end].eq_ignore_ascii_case(k))
+                        && tokens.get(i + 1).is_some_and(|t| t.kind == Kind::Open) =>
+                {
+                    typed.push_str(&query[pos..t.start]);
+                    typed.push_str(&" ".repeat(t.end - t.start));
+                    pos = t.end;
+                    if let (Some(Kind::Dollar(n)), Some(Kind::Close)) = (
+                        tokens.get(i + 2).map(|t| t.kind),
+                        tokens.get(i + 3).map(|t| t.kind),
+                    ) {
+                        if n != 0 {
+                            arrays.push(n - 1);
+                        }
+                    }
+                }
+                _ => (),
+            }
+            let Some(tuple) = tuples.iter().find(|tuple| tuple.open == i) else {
+                i += 1;
+                continue;
+            };
+            typed.push_str(&query[pos..t.start]);
+            typed.push('(');
+            for (k, (start, end)) in tuple.elements.iter().enumerate() {
+                if k != 0 {
+                    typed.push_str(" AND ");
+                }
+                typed.push_str(&query[*start..*end]);
+                for (j, t) in tokens.iter().enumerate().take(tuple.list).skip(i) {
+                    if t.start >= *start && t.end <= *end {
+                        push_arg(&mut typed_args, j);
+                    }
+                }
+                typed.push_str(if tuple.not_in { " NOT IN (" } else { " IN (" });
+                if dollar {
+                    fresh += 1;
+                    typed.push_str(&format!("${fresh}"));
+                    set_typed_arg(&mut typed_args, fresh - 1, (args[tuple.list], k));
+                } else {
+                    typed.push_str("_LIST_");
+                    typed_args.push((args[tuple.list], k));
+                }
+                typed.push(')');
+            }
+            typed.push(')');
+            i = tuple.list + 2;
+            pos = tokens[tuple.list + 1].end;
+        }
+        typed.push_str(&query[pos..]);
+        for (i, a) in typed_args.iter_mut().enumerate() {
+            if a.0 == usize::MAX {
+                *a = (i, 0);
+            }
+        }
+
+        let orders = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.kind == Kind::Order)
+            .map(|(i, t)| (args[i], t.start))
+            .collect();
+
+        // A list numbered after the highest argument before it may take a later `$n`
+        let mut collisions: Vec<usize> = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| dollar && matches!(t.kind, Kind::List | Kind::Order))
+            .map(|(i, _)| args[i] + 1)
+            .filter(|n| tokens.iter().any(|t| t.kind == Kind::Dollar(*n)))
+            .collect();
+        collisions.dedup();
+
+        ListQuery {
+            typed,
+            runtime,
+            typed_args,
+            lists,
+            orders,
+            arrays,
+            parsable,
+            collisions,
+        }
+    }
+
+    /// The argument, and the element of the row for tuple lists, of an argument of the
+    /// typed query
+    pub(crate) fn typed_arg(&self, index: usize) -> (usize, usize) {
+        self.typed_args.get(index).copied().unwrap_or((index, 0))
+    }
+
+    /// Find the predicates where a `_LIST_` is the only element on the right hand side of
+    /// IN or NOT IN
+    ///
+    /// The predicate includes any parentheses around the left hand side
+    pub(crate) fn in_predicates(&self, dialect: &SQLDialect) -> Vec<InPredicate> {
+        in_predicates(&self.parsable, dialect)
+    }
+}
+
+/// A `lhs IN (_LIST_)` or `lhs NOT IN (_LIST_)` predicate in a query
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct InPredicate {
+    /// The number of `_LIST_` occurrences in the query before this list
+    pub list: usize,
+    /// Start of the predicate in the query
+    pub start: usize,
+    /// End of the predicate in the query
+    pub end: usize,
+    /// True for NOT IN
+    pub not_in: bool,
+}
+
+fn in_predicates(query: &str, dialect: &SQLDialect) -> Vec<InPredicate> {
+    let options = crate::parse_options(dialect);
+    let mut issues = sql_parse::Issues::new(query);
+    let Some(statement) = sql_parse::parse_statement(query, &mut issues, &options) else {
+        return Vec::new();
+    };
+    let offsets: Vec<usize> = tokenize(query)
+        .iter()
+        .filter(|t| t.kind == Kind::List)
+        .map(|t| t.start)
+        .collect();
+    let mut predicates = Vec::new();
+    ast::visit_statement(&statement, &mut |e| {
+        let Expression::In {
+            lhs, rhs, not_in, ..
+        } = e
+        else {
+            return;
+        };
+        let [Expression::ListHack((_, list))] = rhs.as_slice() else {
+            return;
+        };
+        let Some(close) = query[list.end..].trim_start().strip_prefix(')') else {
+            return;
+        };
+        let end = query.len() - close.len();
+        let lhs = lhs.span();
+        let mut start = lhs.start;
+        let mut lhs_end = lhs.end;
+        // Include parentheses enclosing exactly the left hand side
+        while let (Some(before), Some(after)) = (
+            query[..start].trim_end().strip_suffix('('),
+            query[lhs_end..].trim_start().strip_prefix(')'),
+        ) {
+            start = before.len();
+            lhs_end = query.len() - after.len();
+        }
+        let Ok(list) = offsets.binary_search(&list.start) else {
+            return;
+        };
+        predicates.push(InPredicate {
+            list,
+            start,
+            end,
+            not_in: *not_in,
+        });
+    });
+    predicates.sort_by_key(|p| p.start);
+    predicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dollar_lists() {
+        let q = ListQuery::new(
+            "SELECT a FROM t WHERE b=$1 AND c IN (_LIST_) AND d=$3 OR e IN (_LIST_)",
+            &SQLDialect::PostgreSQL,
+        );
+        assert_eq!(
+            q.typed,
+            "SELECT a FROM t WHERE b=$1 AND c IN ($2    ) AND d=$3 OR e IN ($4    )"
+        );
+        assert_eq!(q.runtime, q.typed);
+        assert_eq!(q.lists, vec![(1, 1), (3, 1)]);
+        let q = ListQuery::new(
+            "SELECT '_LIST_', \"_LIST_\", A_LIST_ FROM t WHERE c IN (_LIST_)",
+            &SQLDialect::PostgreSQL,
+        );
+        assert_eq!(
+            q.runtime,
+            "SELECT '_LIST_', \"_LIST_\", A_LIST_ FROM t WHERE c IN ($1    )"
+        );
+        assert!(q.collisions.is_empty());
+        let q = ListQuery::new(
+            "SELECT $$_LIST_ $1$$, $a$ ? $a$, E'\\' _LIST_' FROM t WHERE c IN (_LIST_) AND d=$1",
+            &SQLDialect::PostgreSQL,
+        );
+        assert_eq!(q.lists, vec![(0, 1)]);
+        assert_eq!(q.collisions, vec![1]);
+    }
+
+    #[test]
+    fn test_any_arrays() {
+        let query = "SELECT a FROM t WHERE b = ANY($2) AND $1 <> all (c) AND d = some(SELECT 1)";
+        let q = ListQuery::new(query, &SQLDialect::PostgreSQL);
+        assert_eq!(
+            q.typed,
+            "SELECT a FROM t WHERE b =    ($2) AND $1 <>     (c) AND d =     (SELECT 1)"
+        );
+        assert_eq!(q.runtime, query);
+        assert_eq!(q.arrays, vec![1]);
+        let q = ListQuery::new(query, &SQLDialect::MariaDB);
+        assert_eq!(q.typed, query);
+        assert!(q.arrays.is_empty());
+    }
+
+    #[test]
+    fn test_tuple_lists() {
+        let query = "SELECT a FROM t WHERE b=? AND (c, d + ?) NOT IN (_LIST_) AND e IN (_LIST_)";
+        let q = ListQuery::new(query, &SQLDialect::MariaDB);
+        assert_eq!(
+            q.typed,
+            "SELECT a FROM t WHERE b=? AND (c NOT IN (_LIST_) AND  d + ? NOT IN (_LIST_)) AND e IN (_LIST_)"
+        );
+        assert_eq!(q.runtime, query);
+        assert_eq!(q.lists, vec![(2, 2), (3, 1)]);
+        let typed: Vec<_> = (0..5).map(|i| q.typed_arg(i)).collect();
+        assert_eq!(typed, vec![(0, 0), (2, 0), (1, 0), (2, 1), (3, 0)]);
+        let predicates: Vec<_> = q
+            .in_predicates(&SQLDialect::MariaDB)
+            .iter()
+            .map(|p| (p.list, &query[p.start..p.end], p.not_in))
+            .collect();
+        assert_eq!(
+            predicates,
+            vec![
+                (0, "(c, d + ?) NOT IN (_LIST_)", true),
+                (1, "e IN (_LIST_)", false)
+            ]
+        );
+
+        let query = "SELECT a FROM t WHERE d = $1 AND (b, c) IN (_LIST_)";
+        let q = ListQuery::new(query, &SQLDialect::PostgreSQL);
+        assert_eq!(
+            q.typed,
+            "SELECT a FROM t WHERE d = $1 AND (b IN ($3) AND  c IN ($4))"
+        );
+        assert_eq!(
+            q.runtime,
+            "SELECT a FROM t WHERE d = $1 AND (b, c) IN ($2    )"
+        );
+        assert_eq!(q.lists, vec![(1, 2)]);
+        let typed: Vec<_> = (0..4).map(|i| q.typed_arg(i)).collect();
+        assert_eq!(typed, vec![(0, 0), (1, 0), (1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn test_in_predicates() {
+        let query = "SELECT `id` FROM `t1` WHERE (`cu8`) IN (_LIST_) AND `ctext` NOT IN ( _LIST_ )
+            OR `id` IN (SELECT `id` FROM `t1` WHERE `cu16` IN (_LIST_)) OR `cu32` IN (1, _LIST_)";
+        let predicates = in_predicates(query, &SQLDialect::MariaDB);
+        let texts: Vec<_> = predicates
+            .iter()
+            .map(|p| (p.list, &query[p.start..p.end], p.not_in))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                (0, "(`cu8`) IN (_LIST_)", false),
+                (1, "`ctext` NOT IN ( _LIST_ )", true),
+                (2, "`cu16` IN (_LIST_)", false),
+            ]
+        );
+        let query = "SELECT id FROM t WHERE a = $1 AND b NOT IN (_LIST_)";
+        let predicates = in_predicates(query, &SQLDialect::PostgreSQL);
+        assert_eq!(predicates.len(), 1);
+        assert_eq!(
+            &query[predicates[0].start..predicates[0].end],
+            "b NOT IN (_LIST_)"
+        );
+    }
+
+    #[test]
+    fn test_row_comparisons() {
+        let query = "SELECT a FROM t WHERE (b, c + ?) >= (?, ?) AND IFNULL(d, e) = (f) AND g = ?";
+        let q = ListQuery::new(query, &SQLDialect::MariaDB);
+        assert_eq!(
+            q.typed,
+            "SELECT a FROM t WHERE (b >= ? AND  c + ? >=  ?) AND IFNULL(d, e) = (f) AND g = ?"
+        );
+        let typed: Vec<_> = (0..4).map(|i| q.typed_arg(i)).collect();
+        assert_eq!(typed, vec![(1, 0), (0, 0), (2, 0), (3, 0)]);
+        assert_eq!(
+            q.parsable,
+            "SELECT a FROM t WHERE (b+ c + ?) >= (?+ ?) AND IFNULL(d, e) = (f) AND g = ?"
+        );
+
+        let q = ListQuery::new(
+            "SELECT a FROM t WHERE ($2, b) < ($1, c)",
+            &SQLDialect::PostgreSQL,
+        );
+        assert_eq!(q.typed, "SELECT a FROM t WHERE ($2 < $1 AND  b <  c)");
+        assert_eq!(q.runtime, "SELECT a FROM t WHERE ($2, b) < ($1, c)");
+    }
+}
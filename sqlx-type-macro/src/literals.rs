@@ -0,0 +1,357 @@
+//! Checks of literal arguments against the types of their columns
+//!
+//! An integer literal given for an integer column must fit in the type of the column, one
+//! given for a `DECIMAL(p, s)` column must have at most p - s digits, and a string literal
+//! given for a `VARCHAR(n)` or `CHAR(n)` column must have at most n characters.
+//! Literals are also checked inside `&`, `Some(...)` and the array literals given for lists and
+//! arrays, and against the column an argument is compared with by `=`, `<=>`, `!=` or `IN`.
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use proc_macro2::TokenStream;
+use sql_parse::{BinaryOperator, Expression, IdentifierPart};
+use sql_type::{FullType, SQLDialect, Type};
+use syn::spanned::Spanned;
+use syn::{Expr, Lit, UnOp};
+
+use crate::{ast, custom, SCHEMAS};
+
+/// The smallest and largest value of an integer type
+fn range(t: &Type) -> Option<(i128, i128)> {
+    Some(match t {
+        Type::U8 => (0, u8::MAX.into()),
+        Type::I8 => (i8::MIN.into(), i8::MAX.into()),
+        Type::U16 => (0, u16::MAX.into()),
+        Type::I16 => (i16::MIN.into(), i16::MAX.into()),
+        Type::U32 => (0, u32::MAX.into()),
+        Type::I32 => (i32::MIN.into(), i32::MAX.into()),
+        Type::U64 => (0, u64::MAX.into()),
+        Type::I64 => (i64::MIN.into(), i64::MAX.into()),
+        _ => return None,
+    })
+}
+
+/// True if the integer value fits in a decimal column, which has a precision of 10 if not
+/// given with MariaDB and any with PostgreSQL
+fn fits_decimal(value: i128, decimal: &custom::Decimal, dialect: &SQLDialect) -> bool {
+    let precision = match (decimal.precision, dialect) {
+        (Some(precision), _) => precision,
+        (None, SQLDialect::MariaDB) => 10,
+        (None, _) => return true,
+    };
+    10u128
+        .checked_pow(precision.saturating_sub(decimal.scale))
+        .is_none_or(|limit| value.unsigned_abs() < limit)
+}
+
+/// The expression inside groups, parentheses and references
+fn strip(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Group(e) => strip(&e.expr),
+        Expr::Paren(e) => strip(&e.expr),
+        Expr::Reference(e) => strip(&e.expr),
+        e => e,
+    }
+}
+
+/// The integer value of expr if it is an integer literal, possibly negated
+fn integer(expr: &Expr) -> Option<i128> {
+    match strip(expr) {
+        Expr::Lit(syn::ExprLit {
+            lit: Lit::Int(i), ..
+        }) => i.base10_parse().ok(),
+        Expr::Unary(syn::ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => integer(expr).map(|v| -v),
+        _ => None,
+    }
+}
+
+/// Whether expr is an integer literal without a suffix, possibly negated
+pub(crate) fn unsuffixed_integer(expr: &Expr) -> bool {
+    match expr {
+        Expr::Group(e) => unsuffixed_integer(&e.expr),
+        Expr::Paren(e) => unsuffixed_integer(&e.expr),
+        Expr::Unary(syn::ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => unsuffixed_integer(expr),
+        Expr::Lit(syn::ExprLit {
+            lit: Lit::Int(i), ..
+        }) => i.suffix().is_empty(),
+        _ => false,
+    }
+}
+
+/// Whether expr is a bare `None`
+pub(crate) fn none(expr: &Expr) -> bool {
+    match expr {
+        Expr::Group(e) => none(&e.expr),
+        Expr::Paren(e) => none(&e.expr),
+        Expr::Path(p) => p.qself.is_none() && p.path.is_ident("None"),
+        _ => false,
+    }
+}
+
+/// Check the literals of an argument given for values of type t
+fn check(errors: &mut Vec<TokenStream>, expr: &Expr, t: &FullType) {
+    let error = |message: String| syn::Error::new(expr.span(), message).to_compile_error();
+    let (_, dialect) = SCHEMAS.deref();
+    // SQLite stores every integer as i64
+    let stored = match dialect {
+        SQLDialect::Sqlite if range(&t.t).is_some() => &Type::I64,
+        _ => &t.t,
+    };
+    if let (Some(value), Some((min, max))) = (integer(expr), range(stored)) {
+        if value < min || value > max {
+            errors.push(error(format!(
+                "Integer {value} is out of range for type {stored}, which holds {min} to {max}"
+            )));
+        }
+        return;
+    }
+    if let (Some(value), Some(custom::Custom::Decimal(decimal))) =
+        (integer(expr), custom::custom(&t.t))
+    {
+        if !fits_decimal(value, decimal, dialect) {
+            let digits = decimal
+                .precision
+                .unwrap_or(10)
+                .saturating_sub(decimal.scale);
+            errors.push(error(format!(
+                "Integer {value} has more than the {digits} digits before the decimal point of \
+                 the column"
+            )));
+        }
+        return;
+    }
+    match strip(expr) {
+        Expr::Lit(syn::ExprLit {
+            lit: Lit::Str(s), ..
+        }) => {
+            if let Some(custom::Custom::Varchar(length)) = custom::custom(&t.t) {
+                let count = s.value().chars().count();
+                if count > *length as usize {
+                    errors.push(error(format!(
+                        "String of {count} characters is longer than the {length} of the column"
+                    )));
+                }
+            }
+        }
+        Expr::Call(call)
+            if call.args.len() == 1
+                && matches!(&*call.func, Expr::Path(p) if p.path.is_ident("Some")) =>
+        {
+            check(errors, &call.args[0], t)
+        }
+        Expr::Array(array) => {
+            // The elements of array columns, or of the lists and arrays compared with columns
+            let element = match custom::custom(&t.t) {
+                Some(custom::Custom::Array(element)) => element,
+                _ => t,
+            };
+            for e in &array.elems {
+                check(errors, e, element);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The type of the column named by the identifier, where an unqualified name must name columns
+/// of the same type in all the tables of the statement
+fn column_type(
+    parts: &[IdentifierPart],
+    tables: &[(&str, Option<&str>)],
+) -> Option<&'static FullType<'static>> {
+    let (schemas, _) = SCHEMAS.deref();
+    let column = |table: &str, column: &str| {
+        schemas
+            .schemas
+            .get(table)?
+            .get_column(column)
+            .map(|c| &c.type_)
+    };
+    match parts {
+        [IdentifierPart::Name(c)] => {
+            let mut types = tables.iter().filter_map(|(t, _)| column(t, c.value));
+            let first = types.next()?;
+            types.all(|t| t.t == first.t).then_some(first)
+        }
+        [IdentifierPart::Name(t), IdentifierPart::Name(c)] => {
+            let (table, _) = tables
+                .iter()
+                .find(|(table, as_)| as_.unwrap_or(table) == t.value)?;
+            column(table, c.value)
+        }
+        _ => None,
+    }
+}
+
+/// The types of the columns arguments are compared with by `=`, `<=>`, `!=` or `IN`, by the
+/// index of the argument in the query
+///
+/// The typer only gives such arguments the base type of the column, so their literals are
+/// checked against the column instead
+pub(crate) fn compared_columns(
+    query: &str,
+    dialect: &SQLDialect,
+) -> BTreeMap<usize, &'static FullType<'static>> {
+    let options = crate::parse_options(dialect);
+    let mut issues = sql_parse::Issues::new(query);
+    let mut compared = BTreeMap::new();
+    let Some(statement) = sql_parse::parse_statement(query, &mut issues, &options) else {
+        return compared;
+    };
+    let mut tables = Vec::new();
+    ast::visit_tables(&statement, &mut |t, as_| {
+        if t.prefix.is_empty() {
+            tables.push((t.identifier.value, as_.map(|a| a.value)));
+        }
+    });
+    ast::visit_statement(&statement, &mut |e| {
+        let (column, values) = match e {
+            Expression::Binary {
+                op: BinaryOperator::Eq | BinaryOperator::NullSafeEq | BinaryOperator::Neq,
+                lhs,
+                rhs,
+                ..
+            } => match (lhs.as_ref(), rhs.as_ref()) {
+                (Expression::Identifier(parts), value) | (value, Expression::Identifier(parts)) => {
+                    (parts, std::slice::from_ref(value))
+                }
+                _ => return,
+            },
+            Expression::In { lhs, rhs, .. } => match lhs.as_ref() {
+                Expression::Identifier(parts) => (parts, rhs.as_slice()),
+                _ => return,
+            },
+            _ => return,
+        };
+        let Some(t) = column_type(column, &tables) else {
+            return;
+        };
+        for value in values {
+            if let Expression::Arg((i, _)) | Expression::ListHack((i, _)) = value {
+                compared.insert(*i, t);
+            }
+        }
+    });
+    compared
+}
+
+/// Check the literals of an argument given for values of the given types, where tuple lists
+/// have a type for each element of the tuples
+pub(crate) fn check_literals(errors: &mut Vec<TokenStream>, expr: &Expr, types: &[&FullType]) {
+    match (strip(expr), types) {
+        (_, []) => (),
+        (_, [t]) => check(errors, expr, t),
+        (Expr::Array(array), _) => {
+            for e in &array.elems {
+                if let Expr::Tuple(tuple) = strip(e) {
+                    for (e, t) in tuple.elems.iter().zip(types) {
+                        check(errors, e, t);
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(expr: &str, types: &[Type<'static>]) -> usize {
+        let types: Vec<FullType> = types
+            .iter()
+            .map(|t| FullType {
+                t: t.clone(),
+                not_null: false,
+                list_hack: false,
+            })
+            .collect();
+        let mut errors = Vec::new();
+        let expr = syn::parse_str(expr).unwrap();
+        check_literals(&mut errors, &expr, &types.iter().collect::<Vec<_>>());
+        errors.len()
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(errors("255", &[Type::U8]), 0);
+        assert_eq!(errors("&256", &[Type::U8]), 1);
+        assert_eq!(errors("-1", &[Type::U8]), 1);
+        assert_eq!(errors("-128", &[Type::I8]), 0);
+        assert_eq!(errors("Some(-129)", &[Type::I8]), 1);
+        assert_eq!(errors("18446744073709551615", &[Type::U64]), 0);
+        assert_eq!(errors("-1", &[Type::I64]), 0);
+        assert_eq!(errors("300", &[Type::F32]), 0);
+        assert_eq!(errors("x", &[Type::U8]), 0);
+        assert_eq!(errors("&[1, 300, 70000]", &[Type::U16]), 1);
+        assert_eq!(errors("[(1, 300), (-1, 2)]", &[Type::U16, Type::U8]), 2);
+    }
+
+    #[test]
+    fn test_compared_columns() {
+        let d = SQLDialect::MariaDB;
+        let compared = |query| {
+            compared_columns(query, &d)
+                .into_iter()
+                .map(|(i, t)| (i, t.t.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            compared("SELECT `id` FROM `t1` WHERE `cu8` = ? AND ? <> `t1`.`cu16`"),
+            [(0, Type::U8), (1, Type::U16)]
+        );
+        assert_eq!(
+            compared("SELECT `id` FROM `t1` AS `t` WHERE `t`.`ci8` IN (_LIST_) AND `cu8` < ?"),
+            [(0, Type::I8)]
+        );
+        assert_eq!(
+            compared(
+                "SELECT `id` FROM `t1` WHERE `id` IN (SELECT `cu32` FROM `t1` WHERE `cu32` = ?)"
+            ),
+            [(0, Type::U32)]
+        );
+        assert_eq!(compared("SELECT `id` FROM `t1` WHERE `x`.`cu8` = ?"), []);
+    }
+
+    #[test]
+    fn test_decimals() {
+        let decimal = |precision, scale| custom::Decimal { precision, scale };
+        let (mariadb, postgres) = (&SQLDialect::MariaDB, &SQLDialect::PostgreSQL);
+        assert!(fits_decimal(999, &decimal(Some(5), 2), mariadb));
+        assert!(!fits_decimal(-1000, &decimal(Some(5), 2), mariadb));
+        assert!(fits_decimal(0, &decimal(Some(2), 2), mariadb));
+        assert!(!fits_decimal(1, &decimal(Some(2), 2), mariadb));
+        assert!(!fits_decimal(10_000_000_000, &decimal(None, 0), mariadb));
+        assert!(fits_decimal(10_000_000_000, &decimal(None, 0), postgres));
+        assert!(fits_decimal(i128::MAX, &decimal(Some(65), 0), mariadb));
+    }
+
+    #[test]
+    fn test_unsuffixed_integer() {
+        let unsuffixed = |expr: &str| unsuffixed_integer(&syn::parse_str(expr).unwrap());
+        assert!(unsuffixed("42"));
+        assert!(unsuffixed("-(42)"));
+        assert!(!unsuffixed("42u8"));
+        assert!(!unsuffixed("&42"));
+        assert!(!unsuffixed("x"));
+    }
+
+    #[test]
+    fn test_none() {
+        let none = |expr: &str| none(&syn::parse_str(expr).unwrap());
+        assert!(none("None"));
+        assert!(none("(None)"));
+        assert!(!none("None::<i64>"));
+        assert!(!none("&None"));
+        assert!(!none("Some(1)"));
+    }
+}
@@ -0,0 +1,174 @@
+//! Typed ORDER BY, LIMIT and OFFSET
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use sql_parse::{Expression, Statement, TableReference};
+use sql_type::{SQLDialect, SelectTypeColumn};
+
+fn visit_table_reference<'a>(t: &TableReference<'a>, f: &mut dyn FnMut(&Expression<'a>)) {
+    match t {
+        TableReference::Table { .. } => (),
+        TableReference::Query { query, .. } => visit_limits(query, f),
+        TableReference::Join { left, right, .. } => {
+            visit_table_reference(left, f);
+            visit_table_reference(right, f);
+        }
+    }
+}
+
+/// Call `f` for the count and offset of every LIMIT in the statement and its derived tables
+fn visit_limits<'a>(s: &Statement<'a>, f: &mut dyn FnMut(&Expression<'a>)) {
+    let limit = match s {
+        Statement::Select(s) => {
+            for t in s.table_references.iter().flatten() {
+                visit_table_reference(t, f);
+            }
+            &s.limit
+        }
+        Statement::Union(u) => {
+            visit_limits(&u.left, f);
+            for w in &u.with {
+                visit_limits(&w.union_statement, f);
+            }
+            &u.limit
+        }
+        Statement::WithQuery(w) => {
+            for b in &w.with_blocks {
+                visit_limits(&b.statement, f);
+            }
+            visit_limits(&w.statement, f);
+            return;
+        }
+        _ => return,
+    };
+    if let Some((_, offset, count)) = limit {
+        if let Some(offset) = offset {
+            f(offset);
+        }
+        f(count);
+    }
+}
+
+/// The arguments of the typed query given directly as the count or offset of a LIMIT
+pub(crate) fn limit_args(query: &str, dialect: &SQLDialect) -> Vec<usize> {
+    let options = crate::parse_options(dialect);
+    let mut issues = sql_parse::Issues::new(query);
+    let Some(statement) = sql_parse::parse_statement(query, &mut issues, &options) else {
+        return Vec::new();
+    };
+    let mut args = Vec::new();
+    visit_limits(&statement, &mut |e| {
+        if let Expression::Arg((i, _)) = e {
+            args.push(*i);
+        }
+    });
+    args
+}
+
+/// The type of arguments given as the count or offset of a LIMIT
+///
+/// MariaDB only accepts non negative integers, SQLite treats a negative count as no limit
+/// and PostgreSQL treats a NULL count as no limit.
+pub(crate) fn limit_type(dialect: &SQLDialect) -> sql_type::FullType<'static> {
+    let (t, not_null) = match dialect {
+        SQLDialect::MariaDB => (sql_type::Type::U64, true),
+        SQLDialect::Sqlite => (sql_type::Type::I64, true),
+        SQLDialect::PostgreSQL => (sql_type::Type::I64, false),
+    };
+    sql_type::FullType {
+        t,
+        not_null,
+        list_hack: false,
+    }
+}
+
+/// Convert a column name such as `created_at` to a variant name such as `CreatedAt`
+fn variant_name(column: &str) -> String {
+    let mut name = String::new();
+    for part in column.split('_').filter(|p| !p.is_empty()) {
+        let mut chars = part.chars();
+        if let Some(c) = chars.next() {
+            name.extend(c.to_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    name
+}
+
+/// Define an enum with a variant for each column returned by the query, used to choose the
+/// column of an `ORDER BY _ORDER_`
+pub(crate) fn quote_order_enum(
+    attrs: &[syn::Attribute],
+    vis: &syn::Visibility,
+    name: &syn::Ident,
+    columns: &[SelectTypeColumn],
+    span: Span,
+) -> TokenStream {
+    let mut variants = Vec::new();
+    let mut names = Vec::new();
+    for c in columns {
+        let Some(column) = &c.name else {
+            continue;
+        };
+        let variant = variant_name(column.value);
+        let Ok(variant) = syn::parse_str::<syn::Ident>(&variant) else {
+            return syn::Error::new(
+                span,
+                format!("Column {} can not be used as an enum variant", column.value),
+            )
+            .to_compile_error();
+        };
+        if variants.contains(&variant) {
+            return syn::Error::new(span, format!("Column {} is returned twice", column.value))
+                .to_compile_error();
+        }
+        variants.push(variant);
+        names.push(column.value);
+    }
+    let indices = 0..variants.len();
+    let doc = names.iter().map(|n| format!("Order by `{n}`"));
+    quote! {
+        #(#attrs)*
+        #vis enum #name {
+            #(#[doc = #doc] #variants),*
+        }
+
+        impl sqlx_type::OrderColumns for #name {
+            const COLUMNS: &'static [&'static str] = &[#(#names),*];
+
+            fn index(&self) -> usize {
+                match self {
+                    #(Self::#variants => #indices),*
+                }
+            }
+        }
+    }
+}
+
+/// Check that the columns of the enum given for an `ORDER BY _ORDER_` are returned by the query
+pub(crate) fn quote_order_check(arg: &syn::Ident, columns: &[SelectTypeColumn]) -> TokenStream {
+    let names = columns
+        .iter()
+        .filter_map(|c| c.name.as_ref().map(|n| n.value));
+    quote! {
+        struct ReturnedColumns;
+        impl sqlx_type::QueryColumns for ReturnedColumns {
+            const COLUMNS: &'static [&'static str] = &[#(#names),*];
+        }
+        sqlx_type::check_order::<ReturnedColumns, _>(#arg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_args() {
+        let query = "SELECT a FROM (SELECT a FROM t LIMIT ?) AS x WHERE b = ? LIMIT ?, ?";
+        assert_eq!(limit_args(query, &SQLDialect::MariaDB), [0, 2, 3]);
+        let query = "SELECT a FROM t WHERE b = $1 UNION SELECT a FROM u LIMIT $2 OFFSET $3";
+        assert_eq!(limit_args(query, &SQLDialect::PostgreSQL), [2, 1]);
+        assert_eq!(variant_name("created_at"), "CreatedAt");
+        assert_eq!(variant_name("id"), "Id");
+    }
+}
@@ -0,0 +1,207 @@
+//! Named SQL fragments spliced into queries
+//!
+//! `sql_fragment!(name = "...")` defines a macro `name`. A query referring to `{name}` in its
+//! literal expands to a call of that macro, which calls the query macro again with the SQL
+//! of the fragment given as `@name = "...";` before the input, so that fragments are
+//! resolved by the scoping rules of macros.
+use proc_macro2::{Delimiter, Group, Literal, TokenStream, TokenTree};
+use quote::quote;
+use syn::{Ident, LitStr};
+
+use crate::lists::{tokenize, Kind};
+
+/// The largest number of times fragments are spliced into a literal, to catch recursion
+const MAX_DEPTH: usize = 16;
+
+/// Find the first reference `{name}` in query, returning its range and the name
+///
+/// Only Rust identifiers name fragments, so the `{1}` of a regular expression is left as is.
+fn reference(query: &str) -> Option<(usize, usize, &str)> {
+    let tokens = tokenize(query);
+    tokens.windows(3).find_map(|w| {
+        let adjacent = w[0].end == w[1].start && w[1].end == w[2].start;
+        let name = &query[w[1].start..w[1].end];
+        (adjacent
+            && &query[w[0].start..w[0].end] == "{"
+            && w[1].kind == Kind::Word
+            && &query[w[2].start..w[2].end] == "}"
+            && syn::parse_str::<Ident>(name).is_ok())
+        .then_some((w[0].start, w[2].end, name))
+    })
+}
+
+/// Parse a fragment given as `@name = "...";` at the start of tokens
+fn definition(tokens: &[TokenTree]) -> Option<(String, String)> {
+    let punct =
+        |i: usize, c: char| matches!(tokens.get(i), Some(TokenTree::Punct(p)) if p.as_char() == c);
+    if !punct(0, '@') || !punct(2, '=') || !punct(4, ';') {
+        return None;
+    }
+    let (TokenTree::Ident(name), TokenTree::Literal(sql)) = (&tokens[1], &tokens[3]) else {
+        return None;
+    };
+    let sql = syn::parse2::<LitStr>(TokenTree::Literal(sql.clone()).into()).ok()?;
+    Some((name.to_string(), sql.value()))
+}
+
+/// Splice the fragments into the query literals of tokens, and with `groups` into those of
+/// the brace groups holding the statements of query_batch!
+///
+/// When a literal refers to a fragment not given, `missing` returns the error.
+fn splice_tokens(
+    tokens: &[TokenTree],
+    fragments: &[(String, String)],
+    groups: bool,
+    missing: &dyn Fn(Ident) -> TokenStream,
+) -> Result<TokenStream, TokenStream> {
+    // The query literals are the first literal, those following it with `+`, and the first
+    // literal after `=` in each function of queries! or after `;` in each statement
+    let mut out = TokenStream::new();
+    let mut seen = false;
+    let mut prev_query = false;
+    for (i, t) in tokens.iter().enumerate() {
+        let is_query = match (i.checked_sub(1).map(|j| &tokens[j]), t) {
+            (_, TokenTree::Punct(p)) if p.as_char() == ';' => {
+                seen = false;
+                false
+            }
+            (_, TokenTree::Literal(_)) if !seen => true,
+            (Some(TokenTree::Punct(p)), TokenTree::Literal(_)) => p.as_char() == '+' && prev_query,
+            _ => false,
+        };
+        if !matches!(t, TokenTree::Punct(p) if p.as_char() == '+') {
+            prev_query = is_query;
+        }
+        let lit = match t {
+            TokenTree::Literal(lit) if is_query => {
+                syn::parse2::<LitStr>(TokenTree::Literal(lit.clone()).into())
+            }
+            TokenTree::Group(g) if groups && g.delimiter() == Delimiter::Brace => {
+                let inner: Vec<TokenTree> = g.stream().into_iter().collect();
+                let mut group = Group::new(
+                    g.delimiter(),
+                    splice_tokens(&inner, fragments, false, missing)?,
+                );
+                group.set_span(g.span());
+                out.extend([TokenTree::Group(group)]);
+                continue;
+            }
+            _ => {
+                out.extend([t.clone()]);
+                continue;
+            }
+        };
+        let Ok(lit) = lit else {
+            out.extend([t.clone()]);
+            continue;
+        };
+        seen = true;
+        let mut query = lit.value();
+        let mut depth = 0;
+        while let Some((start, end, name)) = reference(&query) {
+            let Some((_, sql)) = fragments.iter().find(|(n, _)| n == name) else {
+                return Err(missing(Ident::new(name, lit.span())));
+            };
+            depth += 1;
+            if depth > MAX_DEPTH {
+                return Err(
+                    syn::Error::new(lit.span(), "SQL fragments are nested too deeply")
+                        .to_compile_error(),
+                );
+            }
+            query.replace_range(start..end, &sql.clone());
+        }
+        let mut lit = Literal::string(&query);
+        lit.set_span(t.span());
+        out.extend([TokenTree::Literal(lit)]);
+    }
+    Ok(out)
+}
+
+/// Splice the fragments given before the input into the query literals of the input, and
+/// with `groups` into the statements in brace groups as given to query_batch!
+///
+/// When a literal refers to a fragment not given, the call of its macro is returned as the
+/// error, with `callback` the path of the macro to call again.
+pub(crate) fn splice(
+    input: TokenStream,
+    callback: TokenStream,
+    groups: bool,
+) -> Result<TokenStream, TokenStream> {
+    let tokens: Vec<TokenTree> = input.clone().into_iter().collect();
+    let mut fragments: Vec<(String, String)> = Vec::new();
+    let mut rest = &tokens[..];
+    while let Some(fragment) = definition(rest) {
+        fragments.push(fragment);
+        rest = &rest[5..];
+    }
+    splice_tokens(rest, &fragments, groups, &|name| {
+        quote! { #name! { #callback { #input } } }
+    })
+}
+
+/// Define the macro of a fragment, which calls the given macro with the fragment prepended
+pub(crate) fn quote_sql_fragment(
+    attrs: &[syn::Attribute],
+    name: &Ident,
+    sql: &LitStr,
+) -> TokenStream {
+    quote! {
+        #(#attrs)*
+        macro_rules! #name {
+            ($($m:ident)::+ { $($t:tt)* }) => {
+                $($m)::+! { @#name = #sql; $($t)* }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splice() {
+        let input = quote! {
+            @active = "deleted = 0 AND {tenant}";
+            @tenant = "tenant = ?tenant";
+            "SELECT a FROM t WHERE {active}" + " AND b = '{c}'", "{active}"
+        };
+        let out = splice(input, quote!(sqlx_type::query), false).unwrap();
+        assert_eq!(
+            out.to_string(),
+            quote! {
+                "SELECT a FROM t WHERE deleted = 0 AND tenant = ?tenant" + " AND b = '{c}'",
+                "{active}"
+            }
+            .to_string()
+        );
+        let input = quote!("SELECT a FROM t WHERE b REGEXP CONCAT('^a', c, '{1}$') OR d{1}");
+        let out = splice(input.clone(), quote!(sqlx_type::query), false).unwrap();
+        assert_eq!(out.to_string(), input.to_string());
+        let input = quote!("SELECT a FROM t WHERE {active}");
+        let call = splice(input.clone(), quote!(sqlx_type::query), false).unwrap_err();
+        assert_eq!(
+            call.to_string(),
+            quote!(active! { sqlx_type::query { #input } }).to_string()
+        );
+        let input = quote! {
+            @small = "a < 10";
+            fn f(x: i64) {
+                "UPDATE t SET a = ? WHERE {small}", x;
+                "SELECT a FROM t WHERE {small}" -> A;
+            }
+        };
+        let out = splice(input, quote!(sqlx_type::query_batch), true).unwrap();
+        assert_eq!(
+            out.to_string(),
+            quote! {
+                fn f(x: i64) {
+                    "UPDATE t SET a = ? WHERE a < 10", x;
+                    "SELECT a FROM t WHERE a < 10" -> A;
+                }
+            }
+            .to_string()
+        );
+    }
+}
@@ -0,0 +1,447 @@
+//! The Rust types of SQL types
+//!
+//! Each type sql-type gives a value maps to the tag type checking the arguments given for it,
+//! and to the type it is returned as. Both depend on the dialect, as sqlx supports different
+//! types for each database, and combinations sqlx does not support are compile errors.
+//!
+//! The mapping can be overridden by comments on lines of their own in the schema of the form
+//! `-- sqlx-type: output <type> = <rust type>` for the returned type, or `input` for the tag
+//! type of arguments. The type is named as sql-type names it, such as `u64`, `integer`,
+//! `string` or `datetime`, or as `decimal`, `uuid`, `json`, `interval` or `inet`.
+//!
+//! A `-- sqlx-type: strict integers` line makes integer arguments use the `Strict` tag of
+//! their type, which only accepts the integer types converting losslessly to it.
+use std::ops::Deref;
+
+use once_cell::sync::Lazy;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use sql_type::{BaseType, FullType, SQLDialect, Type};
+
+use crate::{custom, SCHEMAS, SCHEMA_SRC};
+
+/// Where a type is used
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Usage {
+    /// The tag type checking arguments
+    Arg,
+    /// The type of a member of the rows of query!
+    Row,
+    /// The tag type checking the members of the structs of query_as!
+    Member,
+}
+
+/// The names of the types that can be overridden
+const NAMES: &[&str] = &[
+    "u8",
+    "i8",
+    "u16",
+    "i16",
+    "u32",
+    "i32",
+    "u64",
+    "i64",
+    "f32",
+    "f64",
+    "any",
+    "bool",
+    "bytes",
+    "date",
+    "datetime",
+    "float",
+    "integer",
+    "string",
+    "time",
+    "timestamp",
+    "timeinterval",
+    "json",
+    "enum",
+    "set",
+    "decimal",
+    "uuid",
+    "interval",
+    "inet",
+];
+
+/// A mapping given in the schema
+struct Override {
+    input: bool,
+    name: String,
+    rust: String,
+}
+
+/// The configuration given in the schema
+#[derive(Default)]
+struct Config {
+    overrides: Vec<Override>,
+    /// Only accept integer arguments converting losslessly to the type of their column
+    strict_integers: bool,
+}
+
+static CONFIG: Lazy<Config> = Lazy::new(|| {
+    let mut config = Config::default();
+    for line in SCHEMA_SRC.lines().map(str::trim) {
+        if !line.starts_with("--") {
+            continue;
+        }
+        let Some(annotation) = custom::annotation(line) else {
+            continue;
+        };
+        let mut words = annotation.split_whitespace();
+        let input = match words.next() {
+            Some("input") => true,
+            Some("output") => false,
+            Some("strict") => match words.next() {
+                Some("integers") => {
+                    config.strict_integers = true;
+                    continue;
+                }
+                _ => panic!("Expected `{annotation}` to be of the form `strict integers`"),
+            },
+            _ => continue,
+        };
+        let (name, rust) = match (words.next(), words.next(), annotation.split_once('=')) {
+            (Some(name), Some("="), Some((_, rust))) => (name.to_ascii_lowercase(), rust.trim()),
+            _ => panic!(
+                "Expected `{annotation}` to be of the form `{{input|output}} <type> = <rust type>`"
+            ),
+        };
+        if !NAMES.contains(&name.as_str()) {
+            panic!(
+                "Unknown type {name} in `{annotation}`, expected one of {}",
+                NAMES.join(", ")
+            );
+        }
+        if let Err(e) = syn::parse_str::<syn::Type>(rust) {
+            panic!("Invalid Rust type in `{annotation}`: {e}");
+        }
+        config.overrides.push(Override {
+            input,
+            name,
+            rust: rust.to_string(),
+        });
+    }
+    config
+});
+
+/// The override of the type t where it is used
+fn overridden(t: &Type, input: bool) -> Option<&'static Override> {
+    let name = name(t)?;
+    CONFIG
+        .overrides
+        .iter()
+        .find(|o| o.input == input && o.name == name)
+}
+
+/// The name of t in overrides
+fn name(t: &Type) -> Option<String> {
+    Some(match t {
+        Type::Args(_, _) => match custom::custom(t)? {
+            custom::Custom::Decimal(_) => "decimal".to_string(),
+            custom::Custom::Uuid | custom::Custom::PgUuid => "uuid".to_string(),
+            custom::Custom::Json => "json".to_string(),
+            custom::Custom::Interval => "interval".to_string(),
+            custom::Custom::Inet => "inet".to_string(),
+            custom::Custom::Varchar(_) => "string".to_string(),
+            custom::Custom::Array(_) | custom::Custom::Enum(_) => return None,
+        },
+        Type::Enum(_) => "enum".to_string(),
+        Type::Set(_) => "set".to_string(),
+        t => t.to_string(),
+    })
+}
+
+/// The name of the dialect in errors
+fn dialect_name(dialect: &SQLDialect) -> &'static str {
+    match dialect {
+        SQLDialect::MariaDB => "MariaDB",
+        SQLDialect::PostgreSQL => "PostgreSQL",
+        SQLDialect::Sqlite => "SQLite",
+    }
+}
+
+/// The Rust type of a value of the given type where it is used
+pub(crate) fn rust_type(ft: &FullType, usage: Usage) -> TokenStream {
+    let mut t = mapped(&ft.t, usage);
+    if !ft.not_null {
+        t = quote! {Option<#t>};
+    }
+    t
+}
+
+/// An error for values of type t, which sqlx does not support where they are used
+fn unsupported(t: &Type, usage: Usage, what: &str) -> TokenStream {
+    let (_, dialect) = SCHEMAS.deref();
+    let name = match t {
+        // Not the indices of the arguments the type is inferred from
+        Type::Args(base, _) => base.to_string(),
+        t => t.to_string(),
+    };
+    let message = format!(
+        "{} of type {name} are not supported by sqlx with {}{what}",
+        if usage == Usage::Arg {
+            "Arguments"
+        } else {
+            "Values"
+        },
+        dialect_name(dialect),
+    );
+    quote! {compile_error!(#message)}
+}
+
+/// The Rust type of a value of the given type where it is used, not considering null
+fn mapped(t: &Type, usage: Usage) -> TokenStream {
+    let (_, dialect) = SCHEMAS.deref();
+    let input = usage == Usage::Arg;
+    if let Some(o) = overridden(t, input) {
+        return o.rust.parse().unwrap();
+    }
+    let unsupported = |what: &str| unsupported(t, usage, what);
+    use SQLDialect::{PostgreSQL, Sqlite};
+    match (t, dialect, input) {
+        (Type::Args(_, _), _, _) => custom_type(t, usage),
+        // SQLite stores every integer as i64, and sqlx does not encode u64 for it
+        (
+            Type::U8
+            | Type::I8
+            | Type::U16
+            | Type::I16
+            | Type::U32
+            | Type::I32
+            | Type::U64
+            | Type::I64
+            | Type::Base(BaseType::Integer),
+            Sqlite,
+            true,
+        ) => quote! {sqlx_type::SqliteInteger},
+        (
+            Type::U8
+            | Type::I8
+            | Type::U16
+            | Type::I16
+            | Type::U32
+            | Type::I32
+            | Type::U64
+            | Type::I64
+            | Type::Base(BaseType::Integer),
+            Sqlite,
+            false,
+        ) => quote! {i64},
+        // sqlx does not encode the unsigned integers for PostgreSQL
+        (
+            Type::U8
+            | Type::I8
+            | Type::U16
+            | Type::I16
+            | Type::U32
+            | Type::I32
+            | Type::U64
+            | Type::I64,
+            PostgreSQL,
+            true,
+        ) => {
+            let integer = integer_type(t, dialect).unwrap();
+            if CONFIG.strict_integers {
+                quote! {sqlx_type::Strict<sqlx_type::PgInteger<#integer>>}
+            } else {
+                quote! {sqlx_type::PgInteger<#integer>}
+            }
+        }
+        (Type::Base(BaseType::Integer), PostgreSQL, true) => {
+            quote! {sqlx_type::PgInteger<i64>}
+        }
+        (
+            Type::U8
+            | Type::I8
+            | Type::U16
+            | Type::I16
+            | Type::U32
+            | Type::I32
+            | Type::U64
+            | Type::I64,
+            _,
+            _,
+        ) => {
+            let integer = integer_type(t, dialect).unwrap();
+            if input && CONFIG.strict_integers {
+                quote! {sqlx_type::Strict<#integer>}
+            } else {
+                integer
+            }
+        }
+        (Type::Base(BaseType::Integer), _, true) => quote! {sqlx_type::Integer},
+        (Type::Base(BaseType::Integer), _, false) => quote! {i64},
+        // SQLite only has double precision floats
+        (Type::Base(BaseType::Float), Sqlite, true) => quote! {sqlx_type::SqliteFloat},
+        (Type::F32 | Type::F64 | Type::Base(BaseType::Float), Sqlite, false) => quote! {f64},
+        (Type::Base(BaseType::Float), _, true) => quote! {sqlx_type::Float},
+        (Type::Base(BaseType::Float), _, false) => quote! {f64},
+        (Type::F32, _, _) => quote! {f32},
+        (Type::F64, _, _) => quote! {f64},
+        (Type::Base(BaseType::Any) | Type::JSON, Sqlite, true) => quote! {sqlx_type::SqliteAny},
+        (Type::Base(BaseType::Any) | Type::JSON, _, true) => quote! {sqlx_type::Any},
+        // Such as the columns of ANY type or numeric affinity with SQLite
+        (Type::Base(BaseType::Any), _, false) => unsupported(", cast them in the query"),
+        (Type::JSON, _, false) => quote! {String},
+        (Type::Base(BaseType::Bool), _, _) => quote! {bool},
+        (Type::Base(BaseType::Bytes), _, true) => quote! {&[u8]},
+        (Type::Base(BaseType::Bytes), _, false) => quote! {Vec<u8>},
+        (Type::Base(BaseType::String) | Type::Enum(_) | Type::Set(_), _, true) => quote! {&str},
+        (Type::Base(BaseType::String) | Type::Enum(_) | Type::Set(_), _, false) => {
+            quote! {String}
+        }
+        (Type::Base(BaseType::Date), _, true) => quote! {sqlx_type::Date},
+        (Type::Base(BaseType::DateTime), _, true) => quote! {sqlx_type::DateTime},
+        (Type::Base(BaseType::Time), _, true) => quote! {sqlx_type::Time},
+        (Type::Base(BaseType::TimeStamp), _, true) => quote! {sqlx_type::Timestamp},
+        (
+            Type::Base(
+                t @ (BaseType::Date | BaseType::DateTime | BaseType::Time | BaseType::TimeStamp),
+            ),
+            _,
+            false,
+        ) => date_time_type(t),
+        (Type::Base(BaseType::TimeInterval), _, true) => quote! {sqlx_type::Interval},
+        (Type::Base(BaseType::TimeInterval), PostgreSQL, false) => interval_type(),
+        (Type::Base(BaseType::TimeInterval), _, false) => unsupported(""),
+        (Type::Invalid, _, true) => quote! {std::convert::Infallible},
+        (Type::Invalid, _, false) => quote! {i64},
+        (Type::Null, _, _) => unsupported(""),
+    }
+}
+
+/// The Rust integer type of the integer type t
+fn integer_type(t: &Type, dialect: &SQLDialect) -> Option<TokenStream> {
+    use SQLDialect::{PostgreSQL, Sqlite};
+    Some(match (t, dialect) {
+        // PostgreSQL has neither unsigned nor 8 bit integers
+        (Type::U8 | Type::I8, PostgreSQL) => quote! {i16},
+        (Type::U16, PostgreSQL) => quote! {i32},
+        (Type::U32 | Type::U64, PostgreSQL) => quote! {i64},
+        // SQLite stores every integer as i64
+        (
+            Type::U8
+            | Type::I8
+            | Type::U16
+            | Type::I16
+            | Type::U32
+            | Type::I32
+            | Type::U64
+            | Type::I64,
+            Sqlite,
+        ) => quote! {i64},
+        (Type::U8, _) => quote! {u8},
+        (Type::I8, _) => quote! {i8},
+        (Type::U16, _) => quote! {u16},
+        (Type::I16, _) => quote! {i16},
+        (Type::U32, _) => quote! {u32},
+        (Type::I32, _) => quote! {i32},
+        (Type::U64, _) => quote! {u64},
+        (Type::I64, _) => quote! {i64},
+        _ => return None,
+    })
+}
+
+/// The type of integer literals given as arguments of type t
+///
+/// As integer columns accept several integer types, the type of an unsuffixed literal would
+/// not be inferred from the column, but fall back to i32
+pub(crate) fn literal_type(t: &Type) -> Option<TokenStream> {
+    let (_, dialect) = SCHEMAS.deref();
+    if overridden(t, true).is_some() {
+        return None;
+    }
+    integer_type(t, dialect)
+}
+
+/// The output type of a date or time column, from the time crate when the `time` feature is
+/// enabled, also together with `chrono`, and from chrono otherwise
+fn date_time_type(t: &BaseType) -> TokenStream {
+    #[cfg(feature = "time")]
+    match t {
+        BaseType::Date => quote! {sqlx::types::time::Date},
+        BaseType::DateTime => quote! {sqlx::types::time::PrimitiveDateTime},
+        BaseType::Time => quote! {sqlx::types::time::Time},
+        _ => quote! {sqlx::types::time::OffsetDateTime},
+    }
+    #[cfg(all(feature = "chrono", not(feature = "time")))]
+    match t {
+        BaseType::Date => quote! {sqlx::types::chrono::NaiveDate},
+        BaseType::DateTime => quote! {sqlx::types::chrono::NaiveDateTime},
+        BaseType::Time => quote! {sqlx::types::chrono::NaiveTime},
+        _ => quote! {sqlx::types::chrono::DateTime<sqlx::types::chrono::Utc>},
+    }
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    {
+        let _ = t;
+        quote! {compile_error!(
+            "Date and time columns require the chrono or time feature of sqlx-type"
+        )}
+    }
+}
+
+/// The output type of a PostgreSQL interval
+fn interval_type() -> TokenStream {
+    if cfg!(feature = "postgres") {
+        quote! {sqlx::postgres::types::PgInterval}
+    } else {
+        quote! {compile_error!(
+            "INTERVAL columns require the postgres feature of sqlx-type"
+        )}
+    }
+}
+
+/// The type of a column of a type unknown to sql-type
+fn custom_type(t: &Type, usage: Usage) -> TokenStream {
+    let input = usage == Usage::Arg;
+    match custom::custom(t) {
+        Some(custom::Custom::Decimal(_)) if input => quote! {sqlx_type::Decimal},
+        Some(custom::Custom::Decimal(_)) if cfg!(feature = "rust_decimal") => {
+            quote! {sqlx::types::Decimal}
+        }
+        Some(custom::Custom::Decimal(_)) if cfg!(feature = "bigdecimal") => {
+            quote! {sqlx::types::BigDecimal}
+        }
+        Some(custom::Custom::Decimal(_)) => quote! {compile_error!(
+            "DECIMAL columns require the rust_decimal or bigdecimal feature of sqlx-type"
+        )},
+        Some(custom::Custom::Uuid | custom::Custom::PgUuid) if !cfg!(feature = "uuid") => {
+            quote! {compile_error!(
+                "UUID columns require the uuid feature of sqlx-type"
+            )}
+        }
+        Some(custom::Custom::PgUuid) => quote! {sqlx::types::Uuid},
+        Some(custom::Custom::Uuid) if t.base() == BaseType::Bytes => {
+            quote! {sqlx::types::Uuid}
+        }
+        Some(custom::Custom::Uuid) => quote! {sqlx::types::uuid::fmt::Hyphenated},
+        Some(custom::Custom::Array(element)) => {
+            let element = rust_type(element, usage);
+            match usage {
+                Usage::Row => quote! {Vec<#element>},
+                Usage::Arg | Usage::Member => quote! {sqlx_type::Array<#element>},
+            }
+        }
+        Some(custom::Custom::Json) if input => quote! {sqlx_type::Json},
+        Some(custom::Custom::Json) if cfg!(feature = "json") => quote! {sqlx::types::JsonValue},
+        Some(custom::Custom::Json) => quote! {compile_error!(
+            "JSON columns require the json feature of sqlx-type"
+        )},
+        Some(custom::Custom::Interval) if input => quote! {sqlx_type::Interval},
+        Some(custom::Custom::Interval) => interval_type(),
+        Some(custom::Custom::Inet) if input => quote! {sqlx_type::Inet},
+        Some(custom::Custom::Inet) if cfg!(feature = "ipnetwork") => {
+            quote! {sqlx::types::ipnetwork::IpNetwork}
+        }
+        Some(custom::Custom::Inet) => quote! {compile_error!(
+            "INET and CIDR columns require the ipnetwork feature of sqlx-type"
+        )},
+        Some(custom::Custom::Varchar(_)) => mapped(&Type::Base(BaseType::String), usage),
+        Some(custom::Custom::Enum(e)) => {
+            let name = format_ident!("{}", custom::camel_case(&custom::enums()[*e].name));
+            quote! {#name}
+        }
+        // The type of an argument sql-type could not infer, such as one only selected
+        None => unsupported(t, usage, ", cast the argument in the query"),
+    }
+}
@@ -13,7 +13,8 @@ CREATE TABLE `t1` (
     `ctext` varchar(100) NOT NULL,
     `cbytes` blob,
     `cf32` float,
-    `cf64` double
+    `cf64` double,
+    PRIMARY KEY (`id`)
 ) ENGINE=InnoDB DEFAULT CHARSET=utf8;
 
 ALTER TABLE `t1`
@@ -0,0 +1,8 @@
+-- sql-product: sqlite
+CREATE TABLE t1 (
+    id INTEGER NOT NULL PRIMARY KEY,
+    ci INT NOT NULL,
+    csmall SMALLINT,
+    cbig BIGINT NOT NULL,
+    ctext TEXT NOT NULL
+);
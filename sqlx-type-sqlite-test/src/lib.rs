@@ -0,0 +1,2 @@
+//! Tests of sqlx-type against the SQLite schema in `sqlx-type-schema.sql`, which the macros
+//! read from the directory of this crate
@@ -0,0 +1,166 @@
+use sqlx::SqlitePool;
+use sqlx_type::{insert_many, query, query_batch, sql_fragment, Order};
+
+async fn pool() -> SqlitePool {
+    let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+    sqlx::raw_sql(include_str!("../sqlx-type-schema.sql"))
+        .execute(&pool)
+        .await
+        .unwrap();
+    pool
+}
+
+#[tokio::test]
+async fn integer_arguments() {
+    let pool = pool().await;
+    // SQLite stores every integer as i64, whatever the type of the column
+    query!(
+        "INSERT INTO t1 (id, ci, csmall, cbig, ctext) VALUES (?, ?, ?, ?, ?)",
+        1,
+        5u32,
+        Some(7u8),
+        1i64,
+        "a"
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    query!(
+        "INSERT INTO t1 (id, ci, csmall, cbig, ctext) VALUES (?, ?, ?, ?, ?)",
+        2i64,
+        3_000_000_000,
+        None,
+        -1i8,
+        "b"
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    insert_many!(
+        "INSERT INTO t1 (id, ci, csmall, cbig, ctext) VALUES _ROWS_",
+        [(3i64, 1i64, 2u16, 3u32, "c"), (4, 2, 3, 4, "d")]
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let row = query!("SELECT ci, csmall FROM t1 WHERE ci = ?", 5u32)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!((row.ci, row.csmall), (5, Some(7)));
+    let rows = query!(
+        "SELECT id FROM t1 WHERE ci IN (_LIST_) ORDER BY id",
+        [1i64, 3_000_000_000]
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(rows.iter().map(|r| r.id).collect::<Vec<_>>(), [2, 3]);
+}
+
+query!(enum TextOrder, "SELECT id, ctext FROM t1");
+
+#[tokio::test]
+async fn order_argument() {
+    let pool = pool().await;
+    insert_many!(
+        "INSERT INTO t1 (id, ci, csmall, cbig, ctext) VALUES _ROWS_",
+        [
+            (1i64, 1, 1, 1, "_ORDER_"),
+            (2, 2, 2, 2, "b"),
+            (3, 3, 3, 3, "a")
+        ]
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Only the placeholder is replaced, and the empty list predicate after it is still found
+    let empty: [i64; 0] = [];
+    let rows = query!(
+        "SELECT id, ctext FROM (SELECT id, ctext FROM t1 WHERE ctext <> '_ORDER_' \
+         ORDER BY _ORDER_ LIMIT 1) AS s WHERE id NOT IN (_LIST_)",
+        Order::asc(TextOrder::Ctext),
+        &empty
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(rows.iter().map(|r| r.id).collect::<Vec<_>>(), [3]);
+}
+
+query!(struct Text, "SELECT ctext FROM t1");
+
+// Parameters may have the names of the bindings generated by query_batch!
+query_batch! {
+    fn insert_text(tx: i64, acquire: &str, r0: i64) {
+        "INSERT INTO t1 (id, ci, cbig, ctext) VALUES (?, ?, 0, ?)", tx, r0, acquire;
+        "SELECT ctext FROM t1 WHERE id = ?", tx -> Text;
+    }
+}
+
+#[tokio::test]
+async fn batch_parameter_names() {
+    let pool = pool().await;
+    let (_, text) = insert_text(&pool, 1, "a", 2).await.unwrap();
+    assert_eq!(text.ctext, "a");
+}
+
+#[tokio::test]
+async fn insert_many_transaction() {
+    let pool = pool().await;
+    // The rows are inserted by a statement of two rows and one of one row, which fails on the
+    // duplicate key, so the first is rolled back
+    let result = insert_many!(
+        "INSERT INTO t1 (id, ci, cbig, ctext) VALUES _ROWS_",
+        [(1i64, 1, 1, "a"), (2, 2, 2, "b"), (1, 3, 3, "c")]
+    )
+    .execute(&pool)
+    .await;
+    assert!(result.is_err());
+    let rows = query!("SELECT id FROM t1").fetch_all(&pool).await.unwrap();
+    assert!(rows.is_empty());
+}
+
+sql_fragment!(first = "id = 1");
+sql_fragment!(columns = "id, ci, cbig, ctext");
+
+query_batch! {
+    fn update_first(ci: i64) {
+        "UPDATE t1 SET ci = ? WHERE {first}", ci;
+        "SELECT ctext FROM t1 WHERE {first}" -> Text;
+    }
+}
+
+#[tokio::test]
+async fn fragments() {
+    let pool = pool().await;
+    insert_many!(
+        "INSERT INTO t1 ({columns}) VALUES _ROWS_",
+        [(1i64, 1, 1, "a"), (2, 2, 2, "b")]
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    let (updated, text) = update_first(&pool, 3).await.unwrap();
+    assert_eq!((updated.rows_affected(), text.ctext.as_str()), (1, "a"));
+}
+
+#[tokio::test]
+async fn list_too_long() {
+    let pool = pool().await;
+    let ids: Vec<i64> = (0..40000).collect();
+    let rows = query!("SELECT id FROM t1 WHERE id IN (_LIST_)", &ids[..30000])
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert!(rows.is_empty());
+    let Err(e) = query!("SELECT id FROM t1 WHERE id IN (_LIST_)", &ids)
+        .fetch_all(&pool)
+        .await
+    else {
+        panic!("list longer than the maximum number of arguments");
+    };
+    assert!(e.to_string().contains("Too many arguments: 40000 > 32766"));
+}
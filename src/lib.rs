@@ -19,7 +19,8 @@
 //!     `ctext` varchar(100) NOT NULL,
 //!     `cbytes` blob,
 //!     `cf32` float,
-//!     `cf64` double
+//!     `cf64` double,
+//!     PRIMARY KEY (`id`)
 //! ) ENGINE=InnoDB DEFAULT CHARSET=utf8;
 //!
 //! ALTER TABLE `t1`
@@ -50,11 +51,408 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! The rows generated by [query!] are of an anonymous struct type. Outer attributes placed
+//! before the query string are put on that struct, so traits can be derived for it:
+//!
+//! ``` no_run
+//! use {std::env, sqlx::MySqlPool, sqlx_type::query};
+//!
+//! async fn test() -> Result<(), sqlx::Error> {
+//!     let pool = MySqlPool::connect(&env::var("DATABASE_URL").unwrap()).await?;
+//!
+//!     let row = query!(#[derive(Debug, Clone, PartialEq)]
+//!         "SELECT `cu16`, `ctext`, `ci32` FROM `t1` WHERE `id`=?", 42)
+//!         .fetch_one(&pool).await?;
+//!     println!("{:?}", row.clone());
+//!     Ok(())
+//! }
+//! ```
+//!
+//! With the `serde` feature enabled `#[derive(serde::Serialize)]` can be used in the same way,
+//! without the using crate depending on serde directly, as the derive is then taken from the
+//! serde re-exported by sqlx-type. `#[serde(...)]` attributes can be given as well.
+//!
+//! To name the row type, for instance to return it from a function, [query!] can define a
+//! struct in item position, which can then be used with [query_as!]:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::{query, query_as}};
+//!
+//! query!(#[derive(Debug)] pub struct Summary, "SELECT `id`, `ctext` FROM `t1` WHERE `id`=?");
+//!
+//! async fn summary(pool: &MySqlPool, id: i32) -> Result<Summary, sqlx::Error> {
+//!     query_as!(Summary, "SELECT `id`, `ctext` FROM `t1` WHERE `id`=?", id)
+//!         .fetch_one(pool).await
+//! }
+//! ```
+//!
+//! Queries used in several places can be defined once as typed async functions using
+//! [queries!]. The declared argument and return types are checked against the query:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::{queries, query}};
+//!
+//! query!(pub struct Text, "SELECT `id`, `ctext` FROM `t1`");
+//!
+//! queries! {
+//!     /// Find the text of a row
+//!     pub fn get_text(id: i32) -> Option<Text> = "SELECT `id`, `ctext` FROM `t1` WHERE `id`=?";
+//!     pub fn list_texts(ids: &[i32]) -> Vec<Text> =
+//!         "SELECT `id`, `ctext` FROM `t1` WHERE `id` IN (_LIST_)";
+//!     pub fn delete(id: i32) = "DELETE FROM `t1` WHERE `id`=?";
+//! }
+//!
+//! async fn test(pool: &MySqlPool) -> Result<(), sqlx::Error> {
+//!     if let Some(text) = get_text(pool, 42).await? {
+//!         delete(pool, text.id).await?;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Statements that belong together are run in a transaction by the functions of
+//! [query_batch!], which return a tuple with the result of each statement:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::{query, query_batch}};
+//!
+//! query!(pub struct Text, "SELECT `id`, `ctext` FROM `t1`");
+//!
+//! query_batch! {
+//!     /// Update the text of a row and return the row
+//!     pub fn set_text(id: i32, text: &str) {
+//!         "UPDATE `t1` SET `ctext` = ?text WHERE `id` = ?id";
+//!         "SELECT `id`, `ctext` FROM `t1` WHERE `id` = ?id" -> Option<Text>;
+//!     }
+//! }
+//!
+//! async fn test(pool: &MySqlPool) -> Result<(), sqlx::Error> {
+//!     let (updated, text) = set_text(pool, 42, "Hello").await?;
+//!     assert_eq!(updated.rows_affected(), 1);
+//!     println!("{:?}", text.map(|t| t.ctext));
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Using [query_fetch!] or [query_as_fetch!] the number of rows returned is inferred from
+//! the statement, using the unique and primary keys of the schema, and the fetch method
+//! of the returned query yields `T`, `Option<T>` or `Vec<T>` accordingly:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::query_fetch};
+//!
+//! async fn test(pool: &MySqlPool) -> Result<(), sqlx::Error> {
+//!     let count: i64 = query_fetch!("SELECT COUNT(*) AS `count` FROM `t1`")
+//!         .fetch(pool).await?.count;
+//!     let row = query_fetch!("SELECT `ctext` FROM `t1` WHERE `id`=?", 42)
+//!         .fetch(pool).await?;
+//!     if let Some(row) = row {
+//!         println!("{}", row.ctext);
+//!     }
+//!     for row in query_fetch!("SELECT `ctext` FROM `t1` WHERE `cu8`=?", 8)
+//!         .fetch(pool).await? {
+//!         println!("{} of {}", row.ctext, count);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! A list of values can be given as a single argument by writing `_LIST_` in the query,
+//! which is expanded to a placeholder for each element when the query is run. Lists are padded
+//! to a power of two by repeating the last element, so each call site only prepares a few
+//! distinct queries, and the expanded queries are cached. The argument can be any [ListArg],
+//! such as a slice, vector, array or set:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::query};
+//!
+//! async fn test(pool: &MySqlPool, ids: &[i32]) -> Result<(), sqlx::Error> {
+//!     let rows = query!("SELECT `ctext` FROM `t1` WHERE `cu8`=? AND `id` IN (_LIST_)", 8, ids)
+//!         .fetch_all(pool).await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! When a list is empty `x IN (_LIST_)` is false and `x NOT IN (_LIST_)` is true.
+//!
+//! A row value can be compared against a list of tuples, where each element of the tuples is
+//! checked against its column:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::query};
+//!
+//! async fn test(pool: &MySqlPool, keys: &[(u8, String)]) -> Result<(), sqlx::Error> {
+//!     let rows = query!("SELECT `id` FROM `t1` WHERE (`cu8`, `ctext`) IN (_LIST_)", keys)
+//!         .fetch_all(pool).await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! With PostgreSQL a `_LIST_` takes the argument number following the highest `$n` before
+//! it, so in `a = $1 AND b IN (_LIST_) AND c = $3` the list is the second argument, and a
+//! later `$2` is an error.
+//!
+//! Parts of a query can be made optional by putting them in square brackets. Such a fragment
+//! is only included when all of its arguments are `Some`, and the query is type checked with
+//! every combination of fragments. Arguments written as `?name` refer to an argument given
+//! as `name = expr`, or to a variable in scope, while with PostgreSQL the fragments use `$n`:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::query};
+//!
+//! async fn search(pool: &MySqlPool, status: Option<u8>, text: Option<&str>)
+//!     -> Result<(), sqlx::Error> {
+//!     let rows = query!(
+//!         "SELECT `id` FROM `t1` WHERE TRUE [AND `cu8` = ?status] [AND `ctext` LIKE ?text]",
+//!         text = text.map(|t| format!("%{t}%")),
+//!     )
+//!     .fetch_all(pool).await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Predicates shared by many queries can be defined once with [sql_fragment!] and spliced
+//! into queries as `{name}`. The query is typed with the fragment included, and the
+//! arguments of the fragment are given with those of the query, where named arguments are
+//! convenient:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::{query, sql_fragment}};
+//!
+//! sql_fragment!(visible = "`cbool` AND `cu8` = ?status");
+//!
+//! async fn test(pool: &MySqlPool, status: u8) -> Result<(), sqlx::Error> {
+//!     let rows = query!("SELECT `id` FROM `t1` WHERE {visible} AND `cu16` > ?", 10)
+//!         .fetch_all(pool).await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! The column to order by can be chosen at runtime by writing `_ORDER_` in the ORDER BY of
+//! a query. An enum with a variant for each column returned by a query is defined using
+//! `query!(enum Name, "...")`, and the argument of the `_ORDER_` is an [Order] of such an
+//! enum. It is checked at compile time that every column of the enum is returned by the
+//! query. Arguments of LIMIT and OFFSET are typed as `u64` with MariaDB, `i64` with SQLite
+//! and `Option<i64>` with PostgreSQL:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::{query, Order}};
+//!
+//! query!(pub enum TextOrder, "SELECT `id`, `ctext` FROM `t1`");
+//!
+//! async fn page(pool: &MySqlPool, order: Order<TextOrder>, page: u64)
+//!     -> Result<(), sqlx::Error> {
+//!     let rows = query!(
+//!         "SELECT `id`, `ctext` FROM `t1` ORDER BY _ORDER_, `id` LIMIT ? OFFSET ?",
+//!         order, 20, page * 20
+//!     )
+//!     .fetch_all(pool).await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Large results are better paged by the sort key of the last row than by OFFSET. A
+//! function of [queries!] returning `Vec<T>` with `#[keyset(Cursor)]` also defines a struct
+//! `Cursor` holding the columns of the ORDER BY, and a function `name_after` taking a cursor
+//! and returning the following rows, using `(a, b) > (?, ?)` in the WHERE clause. The keys
+//! should be indexed, not null and together unique:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::{queries, query}};
+//!
+//! query!(pub struct Text, "SELECT `id`, `ctext` FROM `t1`");
+//!
+//! queries! {
+//!     #[keyset(TextCursor)]
+//!     pub fn texts(limit: u64) -> Vec<Text> =
+//!         "SELECT `id`, `ctext` FROM `t1` ORDER BY `ctext`, `id` LIMIT ?";
+//! }
+//!
+//! async fn test(pool: &MySqlPool) -> Result<(), sqlx::Error> {
+//!     let mut rows = texts(pool, 20).await?;
+//!     while let Some(cursor) = TextCursor::last(&rows) {
+//!         rows = texts_after(pool, &cursor, 20).await?;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Many rows can be inserted using [insert_many!], where `_ROWS_` is expanded to as many
+//! rows as fit within the argument limit of the database, so large inputs are split over
+//! several statements, which are run in a transaction:
+//!
+//! ``` no_run
+//! use {sqlx::MySqlPool, sqlx_type::insert_many};
+//!
+//! async fn test(pool: &MySqlPool, rows: &[(u8, String)]) -> Result<(), sqlx::Error> {
+//!     insert_many!(
+//!         "INSERT INTO `t1` (`cbool`, `cu8`, `cu16`, `cu32`, `cu64`, `ctext`) VALUES _ROWS_",
+//!         rows.iter().map(|(cu8, ctext)| (false, *cu8, 0, 0, 0, ctext))
+//!     ).execute(pool).await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Literal arguments are checked against the types of their columns when the macro is
+//! expanded, so giving `300` for a `TINYINT UNSIGNED` column or `"abcdef"` for a
+//! `VARCHAR(5)` column is an error, as are such literals in `Some(...)` and array literals.
+//! Arguments compared with a column by `=`, `<=>`, `!=` or `IN` are checked against the
+//! column, so `WHERE cu8 = ?` with 300 or `cu8 IN (_LIST_)` with `[1, 256]` is also an error.
+//!
+//! Integer columns accept arguments of the integer types converting losslessly to the type of
+//! the column, such as `u8` or `i32` for a `BIGINT` column, and also signed types for unsigned
+//! columns and `i32` for narrower columns, whose values are then checked by the database. A
+//! `-- sqlx-type: strict integers` line in the schema restricts them to the lossless
+//! conversions. Unsuffixed integer literal arguments are given the type of their column, and
+//! a bare `None` its option, but elsewhere, such as in the rows of [insert_many!], they are
+//! `i32` unless inferred otherwise, so with strict integers they need a suffix like `0u16`
+//! there. SQLite stores every integer as `i64`, so there integer columns accept any integer
+//! type but `u64`, strict or not. PostgreSQL has no unsigned integers and sqlx encodes neither
+//! those nor `i8` for it, so there integer columns only accept `i16`, `i32` and `i64`.
+//!
+//! References, `Box`, `Arc` and `Rc` are accepted as arguments wherever what they point to
+//! is, as are the `NonZero` integers wherever their integer type is, so `Arc<str>` or
+//! `&&String` can be given for a text column and `Option<Box<[u8]>>` for a nullable blob. The
+//! types sqlx does not encode, such as `Arc<str>` and `Cow<[u8]>`, are bound as an owned copy of
+//! what they refer to. So are `bytes::Bytes`, `smol_str::SmolStr` and
+//! `compact_str::CompactString`, which are accepted with the `bytes`, `smol_str` and
+//! `compact_str` features:
+//!
+//! ``` no_run
+//! use std::{borrow::Cow, num::NonZeroU64, rc::Rc, sync::Arc};
+//! use {sqlx::MySqlPool, sqlx_type::query};
+//!
+//! async fn test(pool: &MySqlPool, id: NonZeroU64) -> Result<(), sqlx::Error> {
+//!     let (text, name): (Arc<str>, Rc<str>) = ("a".into(), "b".into());
+//!     let bytes: Option<Box<[u8]>> = Some(Box::new([1, 2]));
+//!     query!("UPDATE `t1` SET `ctext` = ? WHERE `cu64` = ?", text, id).execute(pool).await?;
+//!     query!("UPDATE `t1` SET `ctext` = ? WHERE `cu64` = ?", name, &id).execute(pool).await?;
+//!     let text = &&String::from("c");
+//!     query!("UPDATE `t1` SET `ctext` = ? WHERE `cu64` = ?", text, 1).execute(pool).await?;
+//!     query!("UPDATE `t1` SET `cbytes` = ? WHERE `id` = ?", bytes, 1).execute(pool).await?;
+//!     let bytes = Cow::Borrowed(&[3u8][..]);
+//!     query!("UPDATE `t1` SET `cbytes` = ? WHERE `id` = ?", bytes, 1).execute(pool).await?;
+//!     #[cfg(feature = "bytes")]
+//!     {
+//!         let bytes = bytes::Bytes::from_static(b"d");
+//!         query!("UPDATE `t1` SET `cbytes` = ? WHERE `id` = ?", bytes, 1).execute(pool).await?;
+//!     }
+//!     #[cfg(feature = "smol_str")]
+//!     {
+//!         let text = smol_str::SmolStr::new("e");
+//!         query!("UPDATE `t1` SET `ctext` = ? WHERE `id` = ?", text, 1).execute(pool).await?;
+//!     }
+//!     #[cfg(feature = "compact_str")]
+//!     {
+//!         let text = compact_str::CompactString::new("f");
+//!         query!("UPDATE `t1` SET `ctext` = ? WHERE `id` = ?", text, 1).execute(pool).await?;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Date and time columns are returned as chrono types with the default `chrono` feature. With
+//! the `time` feature they are returned as `time::Date`, `time::PrimitiveDateTime`,
+//! `time::Time` and `time::OffsetDateTime` instead, and types of either crate can be given as
+//! arguments when its feature is enabled. One of the two features is required to use date and
+//! time columns.
+//!
+//! **Note:** `time` takes precedence over `chrono` when both are enabled. As cargo features
+//! are additive, a crate enabling `time` on sqlx-type changes the row types of every other
+//! crate in the build using it with chrono, so libraries should leave the choice to the final
+//! binary.
+//!
+//! `DECIMAL` and `NUMERIC` columns are returned as `rust_decimal::Decimal` with the
+//! `rust_decimal` feature or as `bigdecimal::BigDecimal` with the `bigdecimal` feature, one of
+//! which is required to use them with MariaDB or PostgreSQL, and they accept these types or
+//! integers as arguments. Expressions computed from decimal columns, including the columns of
+//! views, are typed as floats. SQLite has no decimal type, so there they are always `f64`.
+//!
+//! With the `uuid` feature, PostgreSQL `uuid` and MariaDB `UUID` columns are returned as
+//! `uuid::Uuid` and `uuid::fmt::Hyphenated` respectively. Binary and text columns holding
+//! UUIDs are annotated with a `-- sqlx-type: uuid` comment on the line of the column in the
+//! schema, and are then returned as `uuid::Uuid` and `uuid::fmt::Hyphenated`:
+//!
+//! ```sql
+//! CREATE TABLE `users` (
+//!     `id` BINARY(16) NOT NULL PRIMARY KEY, -- sqlx-type: uuid
+//!     `external_id` CHAR(36) NOT NULL, -- sqlx-type: uuid
+//!     `name` VARCHAR(100) NOT NULL
+//! );
+//! ```
+//!
+//! Values of these types are also accepted when comparing with the columns.
+//!
+//! PostgreSQL array columns such as `text[]` are returned as `Vec<String>`, and accept `Vec`s
+//! and slices of the types accepted for their elements. The elements are assumed not to be
+//! null, unless the column is annotated with `-- sqlx-type: nullable elements`, in which case
+//! they are `Option`s:
+//!
+//! ```sql
+//! CREATE TABLE posts (
+//!     id integer NOT NULL PRIMARY KEY,
+//!     tags text[] NOT NULL,
+//!     scores integer[] -- sqlx-type: nullable elements
+//! );
+//! ```
+//!
+//! Columns can also be compared with the elements of an array argument using `id = ANY($1)`
+//! or `id <> ALL($1)`, where for instance `&ids` with `ids: Vec<i32>` is accepted for `$1`.
+//!
+//! PostgreSQL `timestamptz` columns are returned as `DateTime<Utc>` and `timestamp` columns
+//! as `NaiveDateTime`, or as `OffsetDateTime` and `PrimitiveDateTime` with the `time`
+//! feature. `json` and `jsonb` columns are returned as `serde_json::Value` with the `json`
+//! feature, and accept it or `sqlx::types::Json<T>`. `interval` columns are returned as
+//! `PgInterval` with the `postgres` feature, and also accept `std::time::Duration`. `inet` and
+//! `cidr` columns are returned as `IpNetwork` with the `ipnetwork` feature, and also accept
+//! `IpAddr`.
+//!
+//! Columns of enum types defined by `CREATE TYPE mood AS ENUM (...)` are returned as the Rust
+//! enum of the type defined by [sql_enum!], named after the type in camel case.
+//!
+//! SQLite stores integers as 64 bit and floats as double precision, so with SQLite integer
+//! columns are returned as `i64` and float columns as `f64` whatever their declared type, and
+//! `u64` is not accepted as an argument. Columns of types sql-type does not know, such as
+//! `NVARCHAR(20)` or `UNSIGNED BIG INT`, and all columns of `STRICT` tables, are typed by the
+//! affinity SQLite gives their type. Columns of `ANY` type and of numeric affinity may hold
+//! values of any type and cannot be selected as is, but they can be cast in the query. Columns
+//! declared without a type are not supported.
+//!
+//! PostgreSQL has no unsigned integers, so with PostgreSQL unsigned values are typed as the
+//! smallest signed integer holding them, and `u64` as `i64`. `integer` and `serial` columns
+//! are `i32`, `bigint` and `bigserial` columns `i64` and `smallint` and `smallserial` columns
+//! `i16`.
+//!
+//! Values of types sqlx does not support with the database, such as intervals with MariaDB,
+//! are compile errors. The types can be overridden by comments on lines of their own in the
+//! schema, giving the type values are returned as with `output`, or the type checking the
+//! arguments with `input`:
+//!
+//! ```sql
+//! -- sqlx-type: output integer = i32
+//! -- sqlx-type: input u64 = i64
+//! ```
+//!
+//! The types are named as by sql-type, `u8` to `u64`, `i8` to `i64`, `f32`, `f64`, `integer`,
+//! `float`, `bool`, `string`, `bytes`, `date`, `datetime`, `time`, `timestamp`,
+//! `timeinterval`, `json`, `any`, `enum` and `set`, or as `decimal`, `uuid`, `interval` and
+//! `inet` for the columns of these types. Arguments are accepted for an input type if they
+//! implement `sqlx_type::ArgIn` for it, as the output types do for themselves.
 #![forbid(unsafe_code)]
 #[allow(clippy::single_component_path_imports)]
 use sqlx_type_macro;
 
-pub use crate::sqlx_type_macro::{query, query_as};
+use std::{rc::Rc, sync::Arc};
+
+pub use crate::sqlx_type_macro::{
+    insert_many, queries, query, query_as, query_as_fetch, query_batch, query_fetch, sql_enum,
+    sql_fragment,
+};
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde;
 
 /// Tag type for integer input
 #[doc(hidden)]
@@ -80,25 +478,99 @@ pub struct Date;
 #[doc(hidden)]
 pub struct Time;
 
+/// Tag type for decimal input
+#[doc(hidden)]
+pub struct Decimal;
+
 /// Tag type for time input
 #[doc(hidden)]
 pub struct Any;
 
+/// Tag type for integer input on SQLite, which has no unsigned 64 bit integers
+#[doc(hidden)]
+pub struct SqliteInteger;
+
+/// Tag type for float input on SQLite, which has no decimals
+#[doc(hidden)]
+pub struct SqliteFloat;
+
+/// Tag type for any input on SQLite
+#[doc(hidden)]
+pub struct SqliteAny;
+
+/// Tag type for JSON input
+#[doc(hidden)]
+pub struct Json;
+
+/// Tag type for interval input
+#[doc(hidden)]
+pub struct Interval;
+
+/// Tag type for network address input
+#[doc(hidden)]
+pub struct Inet;
+
+/// Tag type for array input with elements of type T
+#[doc(hidden)]
+pub struct Array<T>(std::marker::PhantomData<T>);
+
+/// Tag type for integer input of type T with strict integers, only accepting the integer types
+/// converting losslessly to T
+#[doc(hidden)]
+pub struct Strict<T>(std::marker::PhantomData<T>);
+
+/// Tag type for integer input of type T with PostgreSQL, only accepting the signed integer
+/// types of at least 16 bits which sqlx encodes for it
+#[doc(hidden)]
+pub struct PgInteger<T>(std::marker::PhantomData<T>);
+
 /// If ArgIn<T> is implemented for J, it means that J can be used as for arguments of type T
 #[doc(hidden)]
 pub trait ArgIn<T> {}
 pub trait ArgOut<T, const IDX: usize> {}
 
-macro_rules! arg_io {
+macro_rules! arg_in {
     ( $dst: ty, $t: ty ) => {
         impl ArgIn<$dst> for $t {}
-        impl ArgIn<$dst> for &$t {}
         impl ArgIn<Option<$dst>> for $t {}
-        impl ArgIn<Option<$dst>> for &$t {}
         impl ArgIn<Option<$dst>> for Option<$t> {}
         impl ArgIn<Option<$dst>> for Option<&$t> {}
-        impl ArgIn<Option<$dst>> for &Option<$t> {}
-        impl ArgIn<Option<$dst>> for &Option<&$t> {}
+    };
+}
+
+// References and smart pointers are accepted wherever what they point to is
+impl<T, P: ArgIn<T> + ?Sized> ArgIn<T> for &P {}
+impl<T, P: ArgIn<T> + ?Sized> ArgIn<T> for Box<P> {}
+impl<T, P: ArgIn<T> + ?Sized> ArgIn<T> for Arc<P> {}
+impl<T, P: ArgIn<T> + ?Sized> ArgIn<T> for Rc<P> {}
+impl<'a, T, P: ?Sized + 'a> ArgIn<T> for Option<Arc<P>> where Option<&'a P>: ArgIn<T> {}
+impl<'a, T, P: ?Sized + 'a> ArgIn<T> for Option<Rc<P>> where Option<&'a P>: ArgIn<T> {}
+
+/// $t is accepted as arguments wherever $like is
+macro_rules! arg_like {
+    ( $t: ty, $like: ty ) => {
+        impl<T> ArgIn<T> for $t where $like: ArgIn<T> {}
+        impl<T> ArgIn<T> for Option<$t> where Option<$like>: ArgIn<T> {}
+        impl<T> ArgIn<T> for Option<&$t> where Option<$like>: ArgIn<T> {}
+    };
+}
+
+/// Like arg_like, for the types sqlx does not encode, which are bound as $owned
+macro_rules! arg_owned {
+    ( $t: ty, $like: ty, $owned: ty ) => {
+        arg_like!($t, $like);
+        impl BindOwned for $t {
+            type Owned = $owned;
+            fn bind_owned(&self) -> $owned {
+                <$owned>::from(&**self)
+            }
+        }
+    };
+}
+
+macro_rules! arg_io {
+    ( $dst: ty, $t: ty ) => {
+        arg_in!($dst, $t);
 
         impl<const IDX: usize> ArgOut<$dst, IDX> for $t {}
         impl<const IDX: usize> ArgOut<Option<$dst>, IDX> for Option<$t> {}
@@ -106,6 +578,43 @@ macro_rules! arg_io {
     };
 }
 
+/// Like arg_io, for the types given for SQLite arguments, checking that sqlx-sqlite encodes $t
+///
+/// The tags used for SQLite arguments must only be given types through this macro, or through
+/// the integer macros below which check the same, or by delegating to the types given so
+macro_rules! sqlite_io {
+    ( $dst: ty, $t: ty ) => {
+        arg_io!($dst, $t);
+        #[cfg(test)]
+        const _: fn() = sqlite_encodes::<$t>;
+    };
+}
+
+/// Like sqlite_io, for the unsized str and [u8] given as references, which are accepted through
+/// the impl for references
+macro_rules! unsized_io {
+    ( $dst: ty, $t: ty ) => {
+        impl ArgIn<$dst> for $t {}
+        impl ArgIn<Option<$dst>> for $t {}
+        impl ArgIn<Option<$dst>> for Option<&$t> {}
+        impl ArgIn<Option<$dst>> for Option<&&$t> {}
+        impl ArgIn<Option<$dst>> for Option<Box<$t>> {}
+
+        impl<const IDX: usize> ArgOut<$dst, IDX> for &$t {}
+        impl<const IDX: usize> ArgOut<Option<$dst>, IDX> for Option<&$t> {}
+        impl<const IDX: usize> ArgOut<$dst, IDX> for Option<&$t> {}
+
+        #[cfg(test)]
+        const _: fn() = sqlite_encodes::<&'static $t>;
+    };
+}
+
+#[cfg(test)]
+fn sqlite_encodes<T: sqlx::Encode<'static, sqlx::Sqlite> + sqlx::Type<sqlx::Sqlite>>() {}
+
+#[cfg(all(test, feature = "postgres"))]
+fn postgres_encodes<T: sqlx::Encode<'static, sqlx::Postgres> + sqlx::Type<sqlx::Postgres>>() {}
+
 arg_io!(Any, u64);
 arg_io!(Any, i64);
 arg_io!(Any, u32);
@@ -117,7 +626,7 @@ arg_io!(Any, i8);
 arg_io!(Any, String);
 arg_io!(Any, f64);
 arg_io!(Any, f32);
-arg_io!(Any, &str);
+unsized_io!(Any, str);
 
 arg_io!(Integer, u64);
 arg_io!(Integer, i64);
@@ -128,82 +637,1333 @@ arg_io!(Integer, i16);
 arg_io!(Integer, u8);
 arg_io!(Integer, i8);
 
-arg_io!(String, String);
+sqlite_io!(String, String);
+
+sqlite_io!(SqliteAny, i64);
+sqlite_io!(SqliteAny, u32);
+sqlite_io!(SqliteAny, i32);
+sqlite_io!(SqliteAny, u16);
+sqlite_io!(SqliteAny, i16);
+sqlite_io!(SqliteAny, u8);
+sqlite_io!(SqliteAny, i8);
+sqlite_io!(SqliteAny, String);
+sqlite_io!(SqliteAny, f64);
+sqlite_io!(SqliteAny, f32);
+unsized_io!(SqliteAny, str);
+
+sqlite_io!(SqliteInteger, i64);
+sqlite_io!(SqliteInteger, u32);
+sqlite_io!(SqliteInteger, i32);
+sqlite_io!(SqliteInteger, u16);
+sqlite_io!(SqliteInteger, i16);
+sqlite_io!(SqliteInteger, u8);
+sqlite_io!(SqliteInteger, i8);
+
+sqlite_io!(SqliteFloat, f64);
+sqlite_io!(SqliteFloat, f32);
 
 arg_io!(Float, f64);
 arg_io!(Float, f32);
 
 arg_io!(u64, u64);
-arg_io!(i64, i64);
-arg_io!(u32, u32);
-arg_io!(i32, i32);
-arg_io!(u16, u16);
-arg_io!(i16, i16);
-arg_io!(u8, u8);
-arg_io!(i8, i8);
-arg_io!(bool, bool);
-arg_io!(f32, f32);
-arg_io!(f64, f64);
-
-arg_io!(&str, &str);
-arg_io!(&str, String);
-arg_io!(&str, std::borrow::Cow<'_, str>);
-
-arg_io!(&[u8], &[u8]);
-arg_io!(&[u8], Vec<u8>);
-arg_io!(Vec<u8>, Vec<u8>);
-
-arg_io!(Timestamp, chrono::NaiveDateTime);
-arg_io!(DateTime, chrono::NaiveDateTime);
-arg_io!(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>);
-arg_io!(Timestamp, chrono::DateTime<chrono::Utc>);
+sqlite_io!(i64, i64);
+sqlite_io!(u32, u32);
+sqlite_io!(i32, i32);
+sqlite_io!(u16, u16);
+sqlite_io!(i16, i16);
+sqlite_io!(u8, u8);
+sqlite_io!(i8, i8);
 
-#[doc(hidden)]
-pub fn check_arg<T, T2: ArgIn<T>>(_: &T2) {}
+/// The integer types $t converting losslessly to the integer type $dst, accepted as arguments
+/// for both $dst and Strict<$dst>
+macro_rules! widen {
+    ( $dst: ty: $( $t: ty ),* ) => {
+        arg_in!(Strict<$dst>, $dst);
+        $(
+            arg_in!($dst, $t);
+            arg_in!(Strict<$dst>, $t);
+            #[cfg(test)]
+            const _: fn() = sqlite_encodes::<$t>;
+        )*
+    };
+}
 
-#[doc(hidden)]
-pub fn check_arg_list_hack<T, T2: ArgIn<T>>(_: &[T2]) {}
+/// The integer types $t whose values may not fit in the integer type $dst, accepted as
+/// arguments for $dst unless strict integers are configured: signed types for unsigned types,
+/// and i32 which is the type of unsuffixed integer literals not inferred otherwise
+macro_rules! lossy {
+    ( $dst: ty: $( $t: ty ),* ) => {
+        $(
+            arg_in!($dst, $t);
+            #[cfg(test)]
+            const _: fn() = sqlite_encodes::<$t>;
+        )*
+    };
+}
 
-#[doc(hidden)]
-pub fn arg_out<T, T2: ArgOut<T, IDX>, const IDX: usize>(v: T2) -> T2 {
-    v
+widen!(u8:);
+widen!(i8:);
+widen!(u16: u8);
+widen!(i16: i8, u8);
+widen!(u32: u8, u16);
+widen!(i32: i8, i16, u8, u16);
+widen!(u64: u8, u16, u32);
+widen!(i64: i8, i16, i32, u8, u16, u32);
+lossy!(u8: i8, i32);
+lossy!(i8: i32);
+lossy!(u16: i8, i16, i32);
+lossy!(i16: i32);
+lossy!(u32: i8, i16, i32);
+lossy!(u64: i8, i16, i32, i64);
+
+/// Like widen and lossy, for the integer types $dst of PostgreSQL, which has no unsigned
+/// integers: the types $t converting losslessly to $dst and the types $lossy which may not fit
+macro_rules! pg_integer {
+    ( $dst: ty: $( $t: ty ),*; $( $lossy: ty ),* ) => {
+        $(
+            arg_in!(PgInteger<$dst>, $t);
+            arg_in!(Strict<PgInteger<$dst>>, $t);
+            #[cfg(all(test, feature = "postgres"))]
+            const _: fn() = postgres_encodes::<$t>;
+        )*
+        $(
+            arg_in!(PgInteger<$dst>, $lossy);
+        )*
+    };
+}
+
+pg_integer!(i16: i16; i32);
+pg_integer!(i32: i16, i32;);
+pg_integer!(i64: i16, i32, i64;);
+
+sqlite_io!(bool, bool);
+sqlite_io!(f32, f32);
+sqlite_io!(f64, f64);
+
+unsized_io!(&str, str);
+sqlite_io!(&str, String);
+sqlite_io!(&str, std::borrow::Cow<'_, str>);
+
+unsized_io!(&[u8], [u8]);
+sqlite_io!(&[u8], Vec<u8>);
+sqlite_io!(Vec<u8>, Vec<u8>);
+arg_owned!(std::borrow::Cow<'_, [u8]>, &'static [u8], Vec<u8>);
+
+arg_like!(std::num::NonZeroU64, u64);
+arg_like!(std::num::NonZeroI64, i64);
+arg_like!(std::num::NonZeroU32, u32);
+arg_like!(std::num::NonZeroI32, i32);
+arg_like!(std::num::NonZeroU16, u16);
+arg_like!(std::num::NonZeroI16, i16);
+arg_like!(std::num::NonZeroU8, u8);
+arg_like!(std::num::NonZeroI8, i8);
+
+arg_io!(Decimal, u64);
+arg_io!(Decimal, i64);
+arg_io!(Decimal, u32);
+arg_io!(Decimal, i32);
+arg_io!(Decimal, u16);
+arg_io!(Decimal, i16);
+arg_io!(Decimal, u8);
+arg_io!(Decimal, i8);
+
+macro_rules! array_io {
+    ( $t: ty ) => {
+        impl<T, U: ArgIn<T>> ArgIn<Array<T>> for $t {}
+        impl<T, U: ArgIn<T>> ArgIn<Option<Array<T>>> for $t {}
+        impl<T, U: ArgIn<T>> ArgIn<Option<Array<T>>> for Option<$t> {}
+    };
 }
 
+array_io!(Vec<U>);
+array_io!(&[U]);
+
+impl<T, U: ArgOut<T, IDX>, const IDX: usize> ArgOut<Array<T>, IDX> for Vec<U> {}
+impl<T, U: ArgOut<T, IDX>, const IDX: usize> ArgOut<Option<Array<T>>, IDX> for Option<Vec<U>> {}
+impl<T, U: ArgOut<T, IDX>, const IDX: usize> ArgOut<Array<T>, IDX> for Option<Vec<U>> {}
+
+/// Implemented by the enums defined by [sql_enum!]
 #[doc(hidden)]
-pub fn convert_list_query(query: &str, list_sizes: &[usize]) -> String {
-    let mut query_iter = query.split("_LIST_");
-    let mut query = query_iter.next().expect("None empty query").to_string();
-    for size in list_sizes {
-        if *size == 0 {
-            query.push_str("NULL");
-        } else {
-            for i in 0..*size {
-                if i == 0 {
-                    query.push('?');
-                } else {
-                    query.push_str(", ?");
-                }
-            }
-        }
-        query.push_str(query_iter.next().expect("More _LIST_ in query"));
-    }
-    if query_iter.next().is_some() {
-        panic!("Too many _LIST_ in query");
-    }
-    query
+pub trait SqlEnum {}
+
+// The impls for options of an enum, which the crate defining the enum can not implement
+impl<E: SqlEnum> ArgIn<Option<E>> for Option<E> {}
+impl<E: SqlEnum> ArgIn<Option<&str>> for Option<E> {}
+impl<E: SqlEnum, const IDX: usize> ArgOut<Option<E>, IDX> for Option<E> {}
+impl<E: SqlEnum, const IDX: usize> ArgOut<E, IDX> for Option<E> {}
+
+#[cfg(feature = "rust_decimal")]
+mod rust_decimal_io {
+    use super::*;
+    arg_io!(Decimal, rust_decimal::Decimal);
+    arg_io!(Float, rust_decimal::Decimal);
+    arg_io!(rust_decimal::Decimal, rust_decimal::Decimal);
 }
 
-#[cfg(test)]
-mod tests {
+#[cfg(feature = "bigdecimal")]
+mod bigdecimal_io {
     use super::*;
+    arg_io!(Decimal, bigdecimal::BigDecimal);
+    arg_io!(Float, bigdecimal::BigDecimal);
+    arg_io!(bigdecimal::BigDecimal, bigdecimal::BigDecimal);
+}
 
-    #[test]
-    fn test_convert_list_query() {
-        // This assert would fire and test will fail.
-        // Please note, that private functions can be tested too!
-        assert_eq!(
-            &convert_list_query("FOO (_LIST_) X _LIST_ O _LIST_ BAR (_LIST_)", &[0, 1, 2, 3]),
-            "FOO (NULL) X ? O ?, ? BAR (?, ?, ?)"
+#[cfg(feature = "uuid")]
+mod uuid_io {
+    use super::*;
+    sqlite_io!(uuid::Uuid, uuid::Uuid);
+    sqlite_io!(&[u8], uuid::Uuid);
+    // PostgreSQL uuid columns are typed as text in expressions
+    sqlite_io!(&str, uuid::Uuid);
+    sqlite_io!(uuid::fmt::Hyphenated, uuid::fmt::Hyphenated);
+    sqlite_io!(&str, uuid::fmt::Hyphenated);
+}
+
+#[cfg(feature = "json")]
+mod json_io {
+    use super::*;
+    use sqlx::types::JsonValue;
+    arg_io!(Json, JsonValue);
+    arg_io!(JsonValue, JsonValue);
+
+    impl<T> ArgIn<Json> for sqlx::types::Json<T> {}
+    impl<T> ArgIn<Option<Json>> for sqlx::types::Json<T> {}
+    impl<T> ArgIn<Option<Json>> for Option<sqlx::types::Json<T>> {}
+
+    impl<T, const IDX: usize> ArgOut<JsonValue, IDX> for sqlx::types::Json<T> {}
+    impl<T, const IDX: usize> ArgOut<Option<JsonValue>, IDX> for Option<sqlx::types::Json<T>> {}
+    impl<T, const IDX: usize> ArgOut<JsonValue, IDX> for Option<sqlx::types::Json<T>> {}
+}
+
+#[cfg(feature = "postgres")]
+mod postgres_io {
+    use super::*;
+    use sqlx::postgres::types::PgInterval;
+    arg_io!(Interval, PgInterval);
+    arg_io!(Interval, std::time::Duration);
+    arg_io!(PgInterval, PgInterval);
+}
+
+#[cfg(feature = "ipnetwork")]
+mod ipnetwork_io {
+    use super::*;
+    use sqlx::types::ipnetwork::IpNetwork;
+    arg_io!(Inet, IpNetwork);
+    arg_io!(Inet, std::net::IpAddr);
+    arg_io!(IpNetwork, IpNetwork);
+    arg_io!(IpNetwork, std::net::IpAddr);
+    arg_io!(Any, IpNetwork);
+    arg_io!(Any, std::net::IpAddr);
+}
+
+#[cfg(feature = "bytes")]
+mod bytes_io {
+    use super::*;
+    arg_owned!(bytes::Bytes, &'static [u8], Vec<u8>);
+}
+
+#[cfg(feature = "smol_str")]
+mod smol_str_io {
+    use super::*;
+    arg_owned!(smol_str::SmolStr, &'static str, String);
+}
+
+#[cfg(feature = "compact_str")]
+mod compact_str_io {
+    use super::*;
+    arg_owned!(compact_str::CompactString, &'static str, String);
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_io {
+    use super::*;
+    sqlite_io!(Timestamp, chrono::NaiveDateTime);
+    sqlite_io!(DateTime, chrono::NaiveDateTime);
+    sqlite_io!(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>);
+    sqlite_io!(Timestamp, chrono::DateTime<chrono::Utc>);
+    sqlite_io!(Date, chrono::NaiveDate);
+    sqlite_io!(Time, chrono::NaiveTime);
+    sqlite_io!(chrono::NaiveDateTime, chrono::NaiveDateTime);
+    sqlite_io!(chrono::NaiveDate, chrono::NaiveDate);
+    sqlite_io!(chrono::NaiveTime, chrono::NaiveTime);
+}
+
+#[cfg(feature = "time")]
+mod time_io {
+    use super::*;
+    sqlite_io!(Timestamp, time::PrimitiveDateTime);
+    sqlite_io!(DateTime, time::PrimitiveDateTime);
+    sqlite_io!(time::OffsetDateTime, time::OffsetDateTime);
+    sqlite_io!(Timestamp, time::OffsetDateTime);
+    sqlite_io!(Date, time::Date);
+    sqlite_io!(Time, time::Time);
+    sqlite_io!(time::PrimitiveDateTime, time::PrimitiveDateTime);
+    sqlite_io!(time::Date, time::Date);
+    sqlite_io!(time::Time, time::Time);
+}
+
+/// Add the components of a row of a tuple list as separate arguments
+#[doc(hidden)]
+pub trait ListTuple<'q, DB: sqlx::Database> {
+    fn add_to(self, args: &mut DB::Arguments<'q>) -> Result<(), sqlx::error::BoxDynError>;
+}
+
+macro_rules! list_tuple {
+    ( $( $t: ident $a: ident $idx: tt ),* ) => {
+        impl<$($t, $a: ArgIn<$t>),*> ArgIn<($($t,)*)> for ($($a,)*) {}
+
+        impl<'q, DB: sqlx::Database, $($a),*> ListTuple<'q, DB> for ($($a,)*)
+        where
+            $($a: 'q + sqlx::Encode<'q, DB> + sqlx::Type<DB>),*
+        {
+            fn add_to(self, args: &mut DB::Arguments<'q>) -> Result<(), sqlx::error::BoxDynError> {
+                use sqlx::Arguments;
+                $(args.add(self.$idx)?;)*
+                Ok(())
+            }
+        }
+
+        impl<'q, DB: sqlx::Database, $($a),*> ListTuple<'q, DB> for &'q ($($a,)*)
+        where
+            $(&'q $a: sqlx::Encode<'q, DB> + sqlx::Type<DB>),*
+        {
+            fn add_to(self, args: &mut DB::Arguments<'q>) -> Result<(), sqlx::error::BoxDynError> {
+                use sqlx::Arguments;
+                $(args.add(&self.$idx)?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+list_tuple!(T0 A0 0, T1 A1 1);
+list_tuple!(T0 A0 0, T1 A1 1, T2 A2 2);
+list_tuple!(T0 A0 0, T1 A1 1, T2 A2 2, T3 A3 3);
+list_tuple!(T0 A0 0, T1 A1 1, T2 A2 2, T3 A3 3, T4 A4 4);
+list_tuple!(T0 A0 0, T1 A1 1, T2 A2 2, T3 A3 3, T4 A4 4, T5 A5 5);
+
+#[doc(hidden)]
+pub fn check_arg<T, T2: ArgIn<T>>(_: &T2) {}
+
+#[doc(hidden)]
+pub fn check_arg_list_hack<T, L: ListArg + ?Sized>(_: &L)
+where
+    L::Element: ArgIn<T>,
+{
+}
+
+/// The arguments sqlx does not encode, which are bound as an owned value of a type it does
+///
+/// This is implemented for smart pointers, which are bound as an owned copy of what they point
+/// to, and for `Cow<[u8]>` and the string and bytes types of other crates. Options and
+/// references of these are bound as options of the owned value and as the owned value.
+#[doc(hidden)]
+pub trait BindOwned {
+    type Owned;
+    fn bind_owned(&self) -> Self::Owned;
+}
+
+impl<P: BindOwned + ?Sized> BindOwned for &P {
+    type Owned = P::Owned;
+    fn bind_owned(&self) -> P::Owned {
+        (**self).bind_owned()
+    }
+}
+
+impl<P: BindOwned> BindOwned for Option<P> {
+    type Owned = Option<P::Owned>;
+    fn bind_owned(&self) -> Option<P::Owned> {
+        self.as_ref().map(P::bind_owned)
+    }
+}
+
+macro_rules! bind_pointer {
+    ( $t: ident ) => {
+        impl<P: ToOwned + ?Sized> BindOwned for $t<P> {
+            type Owned = P::Owned;
+            fn bind_owned(&self) -> P::Owned {
+                (**self).to_owned()
+            }
+        }
+    };
+}
+
+bind_pointer!(Box);
+bind_pointer!(Arc);
+bind_pointer!(Rc);
+
+/// An argument, which the macros bind by calling `(&Bind::new(arg)).bind()`
+///
+/// The impl of [BindArg] for `Bind<P>` binding arguments implementing [BindOwned] as their owned
+/// value takes precedence over the impl for `&Bind<P>` binding arguments as they are given, as
+/// method resolution tries fewer references first.
+#[doc(hidden)]
+pub struct Bind<P>(std::cell::Cell<Option<P>>);
+
+impl<P> Bind<P> {
+    pub fn new(arg: P) -> Self {
+        Bind(std::cell::Cell::new(Some(arg)))
+    }
+
+    fn take(&self) -> P {
+        self.0.take().expect("arguments are bound once")
+    }
+}
+
+/// Bind an argument
+#[doc(hidden)]
+pub trait BindArg {
+    type Value;
+    fn bind(&self) -> Self::Value;
+}
+
+impl<P: BindOwned> BindArg for Bind<P> {
+    type Value = P::Owned;
+    fn bind(&self) -> P::Owned {
+        self.take().bind_owned()
+    }
+}
+
+impl<P> BindArg for &Bind<P> {
+    type Value = P;
+    fn bind(&self) -> P {
+        self.take()
+    }
+}
+
+/// A value that can be given as the argument of a `_LIST_`
+///
+/// This is implemented for slices, arrays, vectors and sets, and references to these.
+/// Iterators can be used by wrapping them in [ListIter].
+pub trait ListArg {
+    /// The type of the elements, checked against the type of the list
+    type Element;
+
+    /// The values bound for the elements
+    type Item<'a>: Clone
+    where
+        Self: 'a;
+
+    /// The number of elements in the list
+    fn list_len(&self) -> usize;
+
+    /// Iterate over the elements of the list
+    fn list_iter(&self) -> impl Iterator<Item = Self::Item<'_>>;
+}
+
+impl<'b, L: ListArg + ?Sized> ListArg for &'b L {
+    type Element = L::Element;
+    type Item<'a>
+        = L::Item<'b>
+    where
+        Self: 'a;
+
+    fn list_len(&self) -> usize {
+        (**self).list_len()
+    }
+
+    fn list_iter(&self) -> impl Iterator<Item = Self::Item<'_>> {
+        let list: &'b L = self;
+        list.list_iter()
+    }
+}
+
+macro_rules! list_arg {
+    ( $t: ty $(, $($g: tt)*)? ) => {
+        impl<T $(, $($g)*)?> ListArg for $t {
+            type Element = T;
+            type Item<'a>
+                = &'a T
+            where
+                Self: 'a;
+
+            fn list_len(&self) -> usize {
+                self.len()
+            }
+
+            fn list_iter(&self) -> impl Iterator<Item = Self::Item<'_>> {
+                self.iter()
+            }
+        }
+    };
+}
+
+list_arg!([T]);
+list_arg!([T; N], const N: usize);
+list_arg!(Vec<T>);
+list_arg!(std::collections::VecDeque<T>);
+list_arg!(std::collections::BTreeSet<T>);
+list_arg!(std::collections::HashSet<T, S>, S);
+
+/// Use an iterator as the argument of a `_LIST_`
+///
+/// The iterator is cloned for each pass over the elements.
+/// ``` no_run
+/// use {std::collections::HashMap, sqlx::MySqlPool, sqlx_type::{query, ListIter}};
+///
+/// async fn test(pool: &MySqlPool, texts: &HashMap<i32, String>) -> Result<(), sqlx::Error> {
+///     query!("DELETE FROM `t1` WHERE `id` IN (_LIST_)", ListIter(texts.keys()))
+///         .execute(pool).await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ListIter<I>(pub I);
+
+impl<I> ListArg for ListIter<I>
+where
+    I: ExactSizeIterator + Clone,
+    I::Item: Clone,
+{
+    type Element = I::Item;
+    type Item<'a>
+        = I::Item
+    where
+        Self: 'a;
+
+    fn list_len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn list_iter(&self) -> impl Iterator<Item = Self::Item<'_>> {
+        self.0.clone()
+    }
+}
+
+#[doc(hidden)]
+pub fn arg_out<T, T2: ArgOut<T, IDX>, const IDX: usize>(v: T2) -> T2 {
+    v
+}
+
+/// Query returned by [query_fetch!] for statements that return exactly one row
+pub struct FetchOne<Q>(pub Q);
+
+/// Query returned by [query_fetch!] for statements that return zero or one row
+pub struct FetchOptional<Q>(pub Q);
+
+/// Query returned by [query_fetch!] for statements that may return any number of rows
+pub struct FetchAll<Q>(pub Q);
+
+/// Query returned by [query_fetch!] for statements that return no rows
+pub struct FetchNone<Q>(pub Q);
+
+impl<'q, DB, F, O, A> FetchOne<sqlx::query::Map<'q, DB, F, A>>
+where
+    DB: sqlx::Database,
+    F: FnMut(DB::Row) -> Result<O, sqlx::Error> + Send,
+    O: Send + Unpin,
+    A: 'q + Send + sqlx::IntoArguments<'q, DB>,
+{
+    /// Execute the query and return the row
+    pub async fn fetch<'e, 'c: 'e, E>(self, executor: E) -> Result<O, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = DB>,
+        DB: 'e,
+        F: 'e,
+        O: 'e,
+    {
+        self.0.fetch_one(executor).await
+    }
+}
+
+impl<'q, DB, F, O, A> FetchOptional<sqlx::query::Map<'q, DB, F, A>>
+where
+    DB: sqlx::Database,
+    F: FnMut(DB::Row) -> Result<O, sqlx::Error> + Send,
+    O: Send + Unpin,
+    A: 'q + Send + sqlx::IntoArguments<'q, DB>,
+{
+    /// Execute the query and return the row if there is one
+    pub async fn fetch<'e, 'c: 'e, E>(self, executor: E) -> Result<Option<O>, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = DB>,
+        DB: 'e,
+        F: 'e,
+        O: 'e,
+    {
+        self.0.fetch_optional(executor).await
+    }
+}
+
+impl<'q, DB, F, O, A> FetchAll<sqlx::query::Map<'q, DB, F, A>>
+where
+    DB: sqlx::Database,
+    F: FnMut(DB::Row) -> Result<O, sqlx::Error> + Send,
+    O: Send + Unpin,
+    A: 'q + Send + sqlx::IntoArguments<'q, DB>,
+{
+    /// Execute the query and return all rows
+    pub async fn fetch<'e, 'c: 'e, E>(self, executor: E) -> Result<Vec<O>, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = DB>,
+        DB: 'e,
+        F: 'e,
+        O: 'e,
+    {
+        self.0.fetch_all(executor).await
+    }
+}
+
+impl<'q, DB, A> FetchNone<sqlx::query::Query<'q, DB, A>>
+where
+    DB: sqlx::Database,
+    A: 'q + Send + sqlx::IntoArguments<'q, DB>,
+{
+    /// Execute the query and return the query result
+    pub async fn fetch<'e, 'c: 'e, E>(self, executor: E) -> Result<DB::QueryResult, sqlx::Error>
+    where
+        'q: 'e,
+        A: 'e,
+        E: sqlx::Executor<'c, Database = DB>,
+    {
+        self.0.execute(executor).await
+    }
+}
+
+/// The direction of an ORDER BY
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Ascending order
+    #[default]
+    Asc,
+    /// Descending order
+    Desc,
+}
+
+/// The columns a query can be ordered by
+///
+/// This is implemented by the enums defined using `query!(enum Name, "...")`, with a variant
+/// for each column returned by the query.
+pub trait OrderColumns {
+    /// The names of the columns
+    const COLUMNS: &'static [&'static str];
+
+    /// The index of the column in [OrderColumns::COLUMNS]
+    fn index(&self) -> usize;
+}
+
+/// The column and direction given for an `ORDER BY _ORDER_`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Order<C> {
+    pub column: C,
+    pub direction: Direction,
+}
+
+impl<C> Order<C> {
+    /// Order by column in ascending order
+    pub fn asc(column: C) -> Self {
+        Order {
+            column,
+            direction: Direction::Asc,
+        }
+    }
+
+    /// Order by column in descending order
+    pub fn desc(column: C) -> Self {
+        Order {
+            column,
+            direction: Direction::Desc,
+        }
+    }
+}
+
+impl<C: OrderColumns> Order<C> {
+    /// The key of the expanded query in the cache of the call site
+    #[doc(hidden)]
+    pub fn key(&self) -> usize {
+        self.column.index() * 2 + self.direction as usize
+    }
+
+    #[doc(hidden)]
+    pub fn column_name(&self) -> &'static str {
+        C::COLUMNS[self.column.index()]
+    }
+}
+
+/// The columns returned by a query using `ORDER BY _ORDER_`
+#[doc(hidden)]
+pub trait QueryColumns {
+    const COLUMNS: &'static [&'static str];
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Return true if all names in `a` are in `b`
+#[doc(hidden)]
+pub const fn columns_subset(a: &[&str], b: &[&str]) -> bool {
+    let mut i = 0;
+    while i < a.len() {
+        let mut j = 0;
+        while j < b.len() && !str_eq(a[i], b[j]) {
+            j += 1;
+        }
+        if j == b.len() {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Check at compile time that all columns of `C` are returned by the query
+#[doc(hidden)]
+pub fn check_order<Q: QueryColumns, C: OrderColumns>(_: &Order<C>) {
+    const {
+        assert!(
+            columns_subset(C::COLUMNS, Q::COLUMNS),
+            "The query does not return every column of the ORDER BY enum"
+        )
+    }
+}
+
+/// Replace the `_ORDER_` at the given offset of query by the column quoted by `quote` and the
+/// direction
+#[doc(hidden)]
+pub fn convert_order(
+    query: &str,
+    offset: usize,
+    column: &str,
+    direction: Direction,
+    quote: char,
+) -> String {
+    let mut order = String::with_capacity(column.len() + 7);
+    order.push(quote);
+    for c in column.chars() {
+        if c == quote {
+            order.push(c);
+        }
+        order.push(c);
+    }
+    order.push(quote);
+    order.push_str(match direction {
+        Direction::Asc => " ASC",
+        Direction::Desc => " DESC",
+    });
+    let mut query = query.to_string();
+    query.replace_range(offset..offset + "_ORDER_".len(), &order);
+    query
+}
+
+/// The syntax used for arguments in queries
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placeholders {
+    /// Arguments are given as ?
+    QuestionMark,
+    /// Arguments are given as $1, $2, ...
+    Dollar,
+}
+
+/// Push `count` comma separated placeholders to `query`, grouped in parentheses when `width` > 1
+///
+/// `next` is the number of the next dollar placeholder
+fn push_placeholders(
+    query: &mut String,
+    count: usize,
+    width: usize,
+    placeholders: Placeholders,
+    next: &mut usize,
+) {
+    use std::fmt::Write;
+    for i in 0..count {
+        if i != 0 {
+            query.push_str(", ");
+        }
+        if width > 1 {
+            query.push('(');
+        }
+        for j in 0..width {
+            if j != 0 {
+                query.push_str(", ");
+            }
+            match placeholders {
+                Placeholders::QuestionMark => query.push('?'),
+                Placeholders::Dollar => {
+                    write!(query, "${next}").unwrap();
+                    *next += 1;
+                }
+            }
+        }
+        if width > 1 {
+            query.push(')');
+        }
+    }
+}
+
+/// The number of elements used for a list of the given length of tuples of the given width
+///
+/// Lists are padded to a power of two by repeating the last element, but no further than the
+/// maximum number of arguments of the database, so only a few distinct queries are constructed
+/// and prepared for each call site. Lists longer than that are not padded, as they can not be
+/// used in a query anyway.
+#[doc(hidden)]
+pub fn list_bucket(len: usize, width: usize, max_arguments: usize) -> usize {
+    let max_len = max_arguments / width;
+    if len == 0 || len > max_len {
+        return len;
+    }
+    len.next_power_of_two().min(max_len)
+}
+
+/// Push the value used in place of an empty list of rows of the given width
+fn push_empty_list(query: &mut String, width: usize) {
+    if width > 1 {
+        query.push('(');
+    }
+    for j in 0..width {
+        if j != 0 {
+            query.push_str(", ");
+        }
+        query.push_str("NULL");
+    }
+    if width > 1 {
+        query.push(')');
+    }
+}
+
+#[doc(hidden)]
+pub fn convert_list_query(query: &str, list_sizes: &[usize]) -> String {
+    convert_tuple_list_query(query, list_sizes, &[])
+}
+
+/// Expand each `_LIST_` of query to `list_sizes[i]` rows of `widths[i]` placeholders
+///
+/// Missing widths default to 1.
+#[doc(hidden)]
+pub fn convert_tuple_list_query(query: &str, list_sizes: &[usize], widths: &[usize]) -> String {
+    let mut query_iter = query.split("_LIST_");
+    let mut query = query_iter.next().expect("None empty query").to_string();
+    for (i, size) in list_sizes.iter().enumerate() {
+        let width = widths.get(i).copied().unwrap_or(1);
+        if *size == 0 {
+            push_empty_list(&mut query, width);
+        } else {
+            push_placeholders(&mut query, *size, width, Placeholders::QuestionMark, &mut 1);
+        }
+        query.push_str(query_iter.next().expect("More _LIST_ in query"));
+    }
+    if query_iter.next().is_some() {
+        panic!("Too many _LIST_ in query");
+    }
+    query
+}
+
+#[doc(hidden)]
+pub fn convert_dollar_list_query(query: &str, sizes: &[usize]) -> String {
+    convert_dollar_tuple_list_query(query, sizes, &[])
+}
+
+/// The length of the dollar quoted string `$tag$...$tag$` at the start of rest
+fn dollar_quoted(rest: &str) -> Option<usize> {
+    let open = rest[1..].find('$')? + 2;
+    let tag = &rest[..open];
+    if !tag[1..open - 1]
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    Some(
+        rest[open..]
+            .find(tag)
+            .map_or(rest.len(), |i| open + i + tag.len()),
+    )
+}
+
+/// The length of the token at the start of rest, which is not a `$n` placeholder
+///
+/// String literals, quoted identifiers, comments, dollar quoted strings and `E'...'` escape
+/// strings are single tokens, as in the tokenizer of the macros, so placeholders inside them
+/// are left untouched.
+fn token_len(rest: &str) -> usize {
+    let Some(c) = rest.chars().next() else {
+        return 0;
+    };
+    match c {
+        '\'' | '"' | '`' => rest[1..].find(c).map_or(rest.len(), |i| i + 2),
+        '-' if rest.starts_with("--") => rest.find('\n').unwrap_or(rest.len()),
+        '/' if rest.starts_with("/*") => rest.find("*/").map_or(rest.len(), |i| i + 2),
+        '$' => dollar_quoted(rest).unwrap_or(1),
+        // An escape string, where a quote is escaped by a backslash
+        'E' | 'e' if rest[1..].starts_with('\'') => {
+            let mut chars = rest.char_indices().skip(2);
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '\'' => return i + 1,
+                    _ => (),
+                }
+            }
+            rest.len()
+        }
+        c if c.is_alphanumeric() || c == '_' => rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len()),
+        c => c.len_utf8(),
+    }
+}
+
+/// Renumber the `$n` placeholders of query, where argument `n` expands to `sizes[n-1]` rows
+/// of `widths[n-1]` arguments
+///
+/// Placeholders of lists are expanded to a comma separated list of placeholders, or NULL
+/// for empty lists. Missing widths default to 1. Placeholders inside string literals, quoted
+/// identifiers, comments, dollar quoted strings and escape strings are left untouched.
+#[doc(hidden)]
+pub fn convert_dollar_tuple_list_query(query: &str, sizes: &[usize], widths: &[usize]) -> String {
+    let width = |n: usize| widths.get(n).copied().unwrap_or(1);
+    let mut starts = Vec::with_capacity(sizes.len());
+    let mut next = 1;
+    for (n, size) in sizes.iter().enumerate() {
+        starts.push(next);
+        next += size * width(n);
+    }
+    let mut out = String::with_capacity(query.len() + 4 * next);
+    let mut rest = query;
+    while let Some(c) = rest.chars().next() {
+        let end = match c {
+            '$' => {
+                let digits = rest[1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len() - 1);
+                match rest[1..digits + 1].parse::<usize>() {
+                    Ok(n) if n != 0 && n <= sizes.len() => {
+                        if sizes[n - 1] == 0 {
+                            push_empty_list(&mut out, width(n - 1));
+                        } else {
+                            let mut start = starts[n - 1];
+                            push_placeholders(
+                                &mut out,
+                                sizes[n - 1],
+                                width(n - 1),
+                                Placeholders::Dollar,
+                                &mut start,
+                            );
+                        }
+                        rest = &rest[digits + 1..];
+                        continue;
+                    }
+                    Ok(_) => digits + 1,
+                    Err(_) => token_len(rest),
+                }
+            }
+            _ => token_len(rest),
+        };
+        out.push_str(&rest[..end]);
+        rest = &rest[end..];
+    }
+    out
+}
+
+/// A `lhs IN (_LIST_)` or `lhs NOT IN (_LIST_)` predicate of a query
+#[doc(hidden)]
+pub struct ListPredicate {
+    /// Index of the list in the sizes given to [convert_empty_lists]
+    pub list: usize,
+    /// Start of the predicate in the query
+    pub start: usize,
+    /// End of the predicate in the query
+    pub end: usize,
+    /// True for NOT IN
+    pub not_in: bool,
+}
+
+/// Remove the optional fragments of query that are not active
+///
+/// Fragments are given as byte ranges and replaced by spaces, so the offsets of the rest of
+/// the query are kept.
+#[doc(hidden)]
+pub fn convert_fragments(query: &str, fragments: &[(usize, usize)], active: &[bool]) -> String {
+    let mut out = query.to_string();
+    for (&(start, end), active) in fragments.iter().zip(active) {
+        if !active {
+            out.replace_range(start..end, &" ".repeat(end - start));
+        }
+    }
+    out
+}
+
+/// Make the IN predicates of empty lists always false and the NOT IN predicates always true
+///
+/// An empty list is expanded to NULL, and `x IN (NULL)` is NULL rather than false, so the
+/// predicate is wrapped as `(FALSE AND x IN (NULL))` or `(TRUE OR x NOT IN (NULL))`, which
+/// keeps any arguments on the left hand side.
+#[doc(hidden)]
+pub fn convert_empty_lists(query: &str, sizes: &[usize], predicates: &[ListPredicate]) -> String {
+    let mut inserts = Vec::new();
+    for p in predicates {
+        if sizes[p.list] != 0 {
+            continue;
+        }
+        inserts.push((p.start, if p.not_in { "(TRUE OR " } else { "(FALSE AND " }));
+        inserts.push((p.end, ")"));
+    }
+    inserts.sort_by_key(|(pos, _)| *pos);
+    let mut out = String::with_capacity(query.len() + 12 * inserts.len());
+    let mut pos = 0;
+    for (p, s) in inserts {
+        out.push_str(&query[pos..p]);
+        out.push_str(s);
+        pos = p;
+    }
+    out.push_str(&query[pos..]);
+    out
+}
+
+/// Replace _ROWS_ in query with `rows` groups of `width` placeholders
+#[doc(hidden)]
+pub fn convert_rows_query(
+    query: &str,
+    width: usize,
+    rows: usize,
+    placeholders: Placeholders,
+) -> String {
+    let (head, tail) = query.split_once("_ROWS_").expect("_ROWS_ in query");
+    let mut query = head.to_string();
+    // Always group the values of a row in parentheses
+    for i in 0..rows {
+        if i != 0 {
+            query.push_str(", ");
+        }
+        query.push('(');
+        push_placeholders(&mut query, width, 1, placeholders, &mut (i * width + 1));
+        query.push(')');
+    }
+    query.push_str(tail);
+    query
+}
+
+/// Cache of query strings expanded at runtime, a static instance is used for each call site
+///
+/// The expanded strings are leaked, so the keys used must be from a small set.
+#[doc(hidden)]
+pub struct QueryCache(std::sync::Mutex<Vec<(Vec<usize>, &'static str)>>);
+
+impl QueryCache {
+    pub const fn new() -> Self {
+        Self(std::sync::Mutex::new(Vec::new()))
+    }
+
+    /// Return the query for the given key, constructing it using `build` if it is not cached
+    pub fn get(&self, key: &[usize], build: impl FnOnce() -> String) -> &'static str {
+        let mut entries = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, query)) = entries.iter().find(|(k, _)| k == key) {
+            return query;
+        }
+        let query: &'static str = Box::leak(build().into_boxed_str());
+        entries.push((key.to_vec(), query));
+        query
+    }
+
+    /// Return the query for the given key like [QueryCache::get], if it takes at most
+    /// `max_arguments` arguments
+    ///
+    /// Larger queries can not be executed, so they are neither constructed nor cached.
+    pub fn get_within(
+        &self,
+        key: &[usize],
+        arguments: usize,
+        max_arguments: usize,
+        build: impl FnOnce() -> String,
+    ) -> Option<&'static str> {
+        if arguments > max_arguments {
+            return None;
+        }
+        Some(self.get(key, build))
+    }
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Insert of many rows returned by [insert_many!]
+///
+/// The rows are inserted using as few statements as possible, given the limit on the number of
+/// arguments in a statement. Each statement inserts a power of two number of rows, so that only
+/// few distinct statements are prepared.
+pub struct InsertMany<DB, I, F> {
+    cache: &'static QueryCache,
+    query: &'static str,
+    width: usize,
+    max_rows: usize,
+    placeholders: Placeholders,
+    rows: I,
+    add: F,
+    db: std::marker::PhantomData<DB>,
+}
+
+impl<DB: sqlx::Database, I: Iterator, F> InsertMany<DB, I, F> {
+    #[doc(hidden)]
+    pub fn new<'q>(
+        cache: &'static QueryCache,
+        query: &'static str,
+        width: usize,
+        max_arguments: usize,
+        placeholders: Placeholders,
+        rows: I,
+        add: F,
+    ) -> Self
+    where
+        F: FnMut(&mut DB::Arguments<'q>, I::Item) -> Result<(), sqlx::error::BoxDynError>,
+    {
+        let max_rows = (max_arguments / width.max(1)).max(1);
+        Self {
+            cache,
+            query,
+            width,
+            // Round down to a power of two
+            max_rows: 1 << max_rows.ilog2(),
+            placeholders,
+            rows,
+            add,
+            db: std::marker::PhantomData,
+        }
+    }
+
+    /// Execute the statements inserting the rows, returning the combined query result
+    ///
+    /// The statements are run in a transaction, so either all or none of the rows are
+    /// inserted. When given a transaction, they are run in a savepoint of it.
+    pub async fn execute<'q, 'c, A>(mut self, executor: A) -> Result<DB::QueryResult, sqlx::Error>
+    where
+        A: sqlx::Acquire<'c, Database = DB>,
+        for<'e> &'e mut DB::Connection: sqlx::Executor<'e, Database = DB>,
+        DB::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+        F: FnMut(&mut DB::Arguments<'q>, I::Item) -> Result<(), sqlx::error::BoxDynError>,
+    {
+        use sqlx::Arguments as _;
+        let mut tx = executor.begin().await?;
+        let mut result = DB::QueryResult::default();
+        let mut chunk = Vec::with_capacity(self.max_rows);
+        loop {
+            chunk.extend(self.rows.by_ref().take(self.max_rows - chunk.len()));
+            if chunk.is_empty() {
+                break;
+            }
+            let rows = if chunk.len() == self.max_rows {
+                chunk.len()
+            } else {
+                1 << chunk.len().ilog2()
+            };
+            let (width, placeholders) = (self.width, self.placeholders);
+            let query = self.cache.get(&[rows], || {
+                convert_rows_query(self.query, width, rows, placeholders)
+            });
+            let mut args = DB::Arguments::default();
+            args.reserve(rows * self.width, 0);
+            for row in chunk.drain(..rows) {
+                (self.add)(&mut args, row).map_err(sqlx::Error::Encode)?;
+            }
+            let r = sqlx::query_with::<DB, _>(query, args)
+                .execute(&mut *tx)
+                .await?;
+            result.extend(std::iter::once(r));
+        }
+        tx.commit().await?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_list_query() {
+        // This assert would fire and test will fail.
+        // Please note, that private functions can be tested too!
+        assert_eq!(
+            &convert_list_query("FOO (_LIST_) X _LIST_ O _LIST_ BAR (_LIST_)", &[0, 1, 2, 3]),
+            "FOO (NULL) X ? O ?, ? BAR (?, ?, ?)"
+        );
+    }
+
+    #[test]
+    fn test_convert_tuple_list_query() {
+        assert_eq!(
+            &convert_tuple_list_query("(a, b) IN (_LIST_) AND c IN (_LIST_)", &[2, 1], &[2]),
+            "(a, b) IN ((?, ?), (?, ?)) AND c IN (?)"
+        );
+        assert_eq!(
+            &convert_tuple_list_query("(a, b, c) IN (_LIST_)", &[0], &[3]),
+            "(a, b, c) IN ((NULL, NULL, NULL))"
+        );
+        assert_eq!(
+            &convert_dollar_tuple_list_query(
+                "a=$1 AND (b, c) IN ($2) AND d=$3",
+                &[1, 2, 1],
+                &[1, 2, 1]
+            ),
+            "a=$1 AND (b, c) IN (($2, $3), ($4, $5)) AND d=$6"
+        );
+    }
+
+    #[test]
+    fn test_convert_fragments() {
+        assert_eq!(
+            &convert_fragments(
+                "WHERE 1  AND a = ?   AND b = ? ",
+                &[(8, 19), (20, 31)],
+                &[false, true]
+            ),
+            "WHERE 1              AND b = ? "
+        );
+    }
+
+    #[test]
+    fn test_convert_order() {
+        assert_eq!(
+            &convert_order(
+                "SELECT a FROM t ORDER BY _ORDER_ LIMIT ?",
+                25,
+                "a`b",
+                Direction::Desc,
+                '`'
+            ),
+            "SELECT a FROM t ORDER BY `a``b` DESC LIMIT ?"
+        );
+        assert_eq!(
+            &convert_order(
+                "SELECT '_ORDER_' FROM t ORDER BY _ORDER_",
+                33,
+                "a",
+                Direction::Asc,
+                '"'
+            ),
+            "SELECT '_ORDER_' FROM t ORDER BY \"a\" ASC"
+        );
+        assert!(columns_subset(&["a", "c"], &["a", "b", "c"]));
+        assert!(!columns_subset(&["a", "d"], &["a", "b", "c"]));
+    }
+
+    #[test]
+    fn test_list_bucket() {
+        let buckets: Vec<usize> = (0..10).map(|len| list_bucket(len, 1, 65535)).collect();
+        assert_eq!(buckets, [0, 1, 2, 4, 4, 8, 8, 8, 8, 16]);
+        // Padding stops at the maximum number of arguments
+        assert_eq!(list_bucket(16384, 2, 65535), 16384);
+        assert_eq!(list_bucket(16385, 2, 65535), 32767);
+        assert_eq!(list_bucket(8193, 1, 32766), 16384);
+        assert_eq!(list_bucket(16385, 1, 32766), 32766);
+        assert_eq!(list_bucket(32767, 1, 32766), 32767);
+        assert_eq!(list_bucket(8193, 2, 32766), 16383);
+        assert_eq!(list_bucket(16384, 2, 32766), 16384);
+    }
+
+    #[test]
+    fn test_query_cache_bounded() {
+        let cache = QueryCache::new();
+        for len in 1..70000 {
+            let size = list_bucket(len, 1, 65535);
+            cache.get_within(&[size], size, 65535, || format!("{size}"));
+        }
+        // One entry for each power of two and one for the largest bucket
+        assert_eq!(cache.0.lock().unwrap().len(), 17);
+    }
+
+    #[test]
+    fn test_convert_dollar_list_query() {
+        assert_eq!(
+            &convert_dollar_list_query(
+                "SELECT a FROM t WHERE b=$1 AND c IN ($2) AND d=$3 AND e IN ($4) AND f=$1",
+                &[1, 3, 1, 2]
+            ),
+            "SELECT a FROM t WHERE b=$1 AND c IN ($2, $3, $4) AND d=$5 AND e IN ($6, $7) AND f=$1"
+        );
+        assert_eq!(
+            &convert_dollar_list_query("SELECT '$2', \"$2\" FROM t WHERE a IN ($2) -- $2", &[1, 0]),
+            "SELECT '$2', \"$2\" FROM t WHERE a IN (NULL) -- $2"
+        );
+        assert_eq!(
+            &convert_dollar_list_query("SELECT $3, $ FROM t WHERE a IN ($1)", &[2]),
+            "SELECT $3, $ FROM t WHERE a IN ($1, $2)"
+        );
+        assert_eq!(
+            &convert_dollar_list_query(
+                "SELECT $q$ $2 $q$, E'it\\'s $2', $$$2$$ FROM t WHERE a IN ($1) AND b=$2 \
+                 AND c = E'\\\\' AND d=$3 AND f = E'\\'' AND e = $a_1$ $1 $a_1$",
+                &[2, 1, 1]
+            ),
+            "SELECT $q$ $2 $q$, E'it\\'s $2', $$$2$$ FROM t WHERE a IN ($1, $2) AND b=$3 \
+             AND c = E'\\\\' AND d=$4 AND f = E'\\'' AND e = $a_1$ $1 $a_1$"
+        );
+    }
+
+    #[test]
+    fn test_convert_empty_lists() {
+        let query = "SELECT a FROM t WHERE b IN (_LIST_) AND (c) NOT IN (_LIST_) OR d IN (_LIST_)";
+        let predicates = [
+            ListPredicate {
+                list: 0,
+                start: 22,
+                end: 35,
+                not_in: false,
+            },
+            ListPredicate {
+                list: 1,
+                start: 40,
+                end: 59,
+                not_in: true,
+            },
+            ListPredicate {
+                list: 2,
+                start: 63,
+                end: 76,
+                not_in: false,
+            },
+        ];
+        let sizes = [0, 0, 2];
+        assert_eq!(
+            &convert_list_query(&convert_empty_lists(query, &sizes, &predicates), &sizes),
+            "SELECT a FROM t WHERE (FALSE AND b IN (NULL)) AND (TRUE OR (c) NOT IN (NULL)) OR d IN (?, ?)"
+        );
+        let sizes = [1, 2, 4];
+        assert_eq!(
+            &convert_list_query(&convert_empty_lists(query, &sizes, &predicates), &sizes),
+            "SELECT a FROM t WHERE b IN (?) AND (c) NOT IN (?, ?) OR d IN (?, ?, ?, ?)"
+        );
+    }
+
+    #[test]
+    fn test_convert_rows_query() {
+        assert_eq!(
+            &convert_rows_query(
+                "INSERT INTO t (a, b) VALUES _ROWS_ ON DUPLICATE KEY UPDATE b=VALUES(b)",
+                2,
+                3,
+                Placeholders::QuestionMark
+            ),
+            "INSERT INTO t (a, b) VALUES (?, ?), (?, ?), (?, ?) ON DUPLICATE KEY UPDATE b=VALUES(b)"
+        );
+        assert_eq!(
+            &convert_rows_query(
+                "INSERT INTO t (a) VALUES _ROWS_",
+                1,
+                3,
+                Placeholders::Dollar
+            ),
+            "INSERT INTO t (a) VALUES ($1), ($2), ($3)"
         );
     }
 }